@@ -0,0 +1,569 @@
+//! Encryption and decryption of files in the age format (<https://age-encryption.org/v1>), with
+//! X25519 recipients and identities, as created by `age` and `age-keygen`
+
+use std::fmt::Display;
+
+use openssl::derive::Deriver;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{Id, PKey, Private, Public};
+use openssl::sign::Signer;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+const VERSION_LINE: &str = "age-encryption.org/v1";
+const X25519_LABEL: &str = "age-encryption.org/v1/X25519";
+const RECIPIENT_PREFIX: &str = "age";
+const IDENTITY_PREFIX: &str = "age-secret-key-";
+const ARMOR_BEGIN: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+const ARMOR_END: &str = "-----END AGE ENCRYPTED FILE-----";
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const BECH32_CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+#[derive(Debug)]
+pub enum AgeError {
+    /// A key that is not a valid recipient or identity
+    Key(String),
+    /// A file that is not in the age format
+    Format(String),
+    /// A file that could not be decrypted with any of the identities
+    Decrypt(String),
+    Crypto(openssl::error::ErrorStack),
+}
+
+impl Display for AgeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgeError::Key(msg) => write!(f, "Invalid key: {msg}"),
+            AgeError::Format(msg) => write!(f, "Invalid age file: {msg}"),
+            AgeError::Decrypt(msg) => f.write_str(msg),
+            AgeError::Crypto(err) => write!(f, "Encryption error: {err}"),
+        }
+    }
+}
+
+impl From<openssl::error::ErrorStack> for AgeError {
+    fn from(e: openssl::error::ErrorStack) -> Self {
+        AgeError::Crypto(e)
+    }
+}
+
+/// The public key that a file is encrypted to, like `age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p`
+pub struct Recipient(PKey<Public>);
+
+/// The private key that a file is decrypted with, like `AGE-SECRET-KEY-1...`
+pub struct Identity(PKey<Private>);
+
+impl Recipient {
+    pub fn parse(key: &str) -> Result<Recipient, AgeError> {
+        let bytes: Vec<u8> = bech32_decode(RECIPIENT_PREFIX, key.trim())?;
+        let key = PKey::public_key_from_raw_bytes(&bytes, Id::X25519)
+            .map_err(|_| AgeError::Key(format!("{key} is not an X25519 public key")))?;
+        Ok(Recipient(key))
+    }
+}
+
+impl Display for Recipient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes: Vec<u8> = self.0.raw_public_key().map_err(|_| std::fmt::Error)?;
+        f.write_str(&bech32_encode(RECIPIENT_PREFIX, &bytes))
+    }
+}
+
+impl Identity {
+    /// All identities in an identity file, ignoring empty lines and `#` comments
+    pub fn parse_file(content: &str) -> Result<Vec<Identity>, AgeError> {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Identity::parse)
+            .collect()
+    }
+
+    pub fn parse(key: &str) -> Result<Identity, AgeError> {
+        let bytes: Vec<u8> = bech32_decode(IDENTITY_PREFIX, key)?;
+        let key = PKey::private_key_from_raw_bytes(&bytes, Id::X25519)
+            .map_err(|_| AgeError::Key(String::from("Not an X25519 secret key")))?;
+        Ok(Identity(key))
+    }
+
+    pub fn generate() -> Result<Identity, AgeError> {
+        Ok(Identity(PKey::generate_x25519()?))
+    }
+
+    pub fn recipient(&self) -> Result<Recipient, AgeError> {
+        let public: Vec<u8> = self.0.raw_public_key()?;
+        Ok(Recipient(PKey::public_key_from_raw_bytes(&public, Id::X25519)?))
+    }
+}
+
+impl Display for Identity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bytes: Vec<u8> = self.0.raw_private_key().map_err(|_| std::fmt::Error)?;
+        f.write_str(&bech32_encode(IDENTITY_PREFIX, &bytes).to_uppercase())
+    }
+}
+
+/// Encrypt `plaintext` so that it can be decrypted by the identity of any of `recipients`
+pub fn encrypt(plaintext: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>, AgeError> {
+    if recipients.is_empty() {
+        return Err(AgeError::Key(String::from("No recipients to encrypt to")));
+    }
+    let ephemerals: Vec<PKey<Private>> =
+        recipients.iter().map(|_| PKey::generate_x25519()).collect::<Result<_, _>>()?;
+    encrypt_with(plaintext, recipients, &random(16)?, &random(16)?, &ephemerals)
+}
+
+/// Encrypt `plaintext` with a given file key, payload nonce and an ephemeral key for each recipient
+fn encrypt_with(
+    plaintext: &[u8],
+    recipients: &[Recipient],
+    file_key: &[u8],
+    nonce: &[u8],
+    ephemerals: &[PKey<Private>],
+) -> Result<Vec<u8>, AgeError> {
+    let mut header: String = format!("{VERSION_LINE}\n");
+    for (recipient, ephemeral) in recipients.iter().zip(ephemerals) {
+        let share: Vec<u8> = ephemeral.raw_public_key()?;
+        let shared: Vec<u8> = derive(ephemeral, &recipient.0)?;
+        let salt: Vec<u8> = [share.as_slice(), &recipient.0.raw_public_key()?].concat();
+        let wrap_key: Vec<u8> = hkdf(&salt, &shared, X25519_LABEL.as_bytes())?;
+        let body: Vec<u8> = seal(&wrap_key, &[0; 12], file_key)?;
+        header.push_str(&format!("-> X25519 {}\n{}\n", encode(&share), encode(&body)));
+    }
+    header.push_str("---");
+    let mac: Vec<u8> = hmac(&hkdf(&[], file_key, b"header")?, header.as_bytes())?;
+    header.push_str(&format!(" {}\n", encode(&mac)));
+
+    let payload_key: Vec<u8> = hkdf(nonce, file_key, b"payload")?;
+    let mut output: Vec<u8> = header.into_bytes();
+    output.extend(nonce);
+
+    let chunks: Vec<&[u8]> = match plaintext.is_empty() {
+        true => vec![&[]],
+        false => plaintext.chunks(CHUNK_SIZE).collect(),
+    };
+    for (i, chunk) in chunks.iter().enumerate() {
+        let nonce: [u8; 12] = chunk_nonce(i as u64, i == chunks.len() - 1);
+        output.extend(seal(&payload_key, &nonce, chunk)?);
+    }
+
+    Ok(output)
+}
+
+/// Decrypt a file in the binary or armored age format with any of `identities`
+pub fn decrypt(file: &[u8], identities: &[Identity]) -> Result<Vec<u8>, AgeError> {
+    let file: Vec<u8> = dearmor(file)?;
+    let (header, payload) = split_header(&file)?;
+    let lines: Vec<&str> = header.lines().collect();
+    if lines.first() != Some(&VERSION_LINE) {
+        return Err(AgeError::Format(String::from("Unsupported version")));
+    }
+
+    let (mac_line, stanzas) = lines[1..]
+        .split_last()
+        .ok_or_else(|| AgeError::Format(String::from("Missing header MAC")))?;
+    let mac: Vec<u8> = mac_line
+        .strip_prefix("--- ")
+        .map(decode)
+        .ok_or_else(|| AgeError::Format(String::from("Missing header MAC")))??;
+
+    let file_key: Vec<u8> = unwrap_file_key(stanzas, identities)?;
+    let header_without_mac: &str = &header[..header.len() - mac_line.len() + 3];
+    let expected: Vec<u8> = hmac(&hkdf(&[], &file_key, b"header")?, header_without_mac.as_bytes())?;
+    if expected.len() != mac.len() || !openssl::memcmp::eq(&expected, &mac) {
+        return Err(AgeError::Format(String::from("Header MAC does not match")));
+    }
+
+    if payload.len() < 16 {
+        return Err(AgeError::Format(String::from("Missing payload nonce")));
+    }
+    let (nonce, ciphertext) = payload.split_at(16);
+    if ciphertext.is_empty() {
+        return Err(AgeError::Format(String::from("Missing final payload chunk")));
+    }
+    let payload_key: Vec<u8> = hkdf(nonce, &file_key, b"payload")?;
+    let chunks: Vec<&[u8]> = ciphertext.chunks(CHUNK_SIZE + TAG_SIZE).collect();
+    let mut plaintext: Vec<u8> = Vec::with_capacity(ciphertext.len());
+    for (i, chunk) in chunks.iter().enumerate() {
+        let nonce: [u8; 12] = chunk_nonce(i as u64, i == chunks.len() - 1);
+        let chunk: Vec<u8> = open(&payload_key, &nonce, chunk)
+            .ok_or_else(|| AgeError::Format(String::from("Payload is corrupted")))?;
+        if chunk.is_empty() && i > 0 {
+            return Err(AgeError::Format(String::from("Final payload chunk is empty")));
+        }
+        plaintext.extend(chunk);
+    }
+
+    Ok(plaintext)
+}
+
+/// A recipient stanza of the header: its type and arguments, and its decoded body
+struct Stanza<'a> {
+    args: Vec<&'a str>,
+    body: Vec<u8>,
+}
+
+/// The stanzas of a header, where each body is wrapped at 64 columns and ends with a shorter line
+fn parse_stanzas<'a>(lines: &[&'a str]) -> Result<Vec<Stanza<'a>>, AgeError> {
+    let mut stanzas: Vec<Stanza<'a>> = Vec::new();
+    let mut lines = lines.iter();
+    while let Some(line) = lines.next() {
+        let args: Vec<&str> = line
+            .strip_prefix("-> ")
+            .ok_or_else(|| AgeError::Format(format!("Invalid stanza {line:?}")))?
+            .split(' ')
+            .collect();
+        if args.iter().any(|arg| arg.is_empty()) {
+            return Err(AgeError::Format(format!("Invalid stanza {line:?}")));
+        }
+
+        let mut body: Vec<u8> = Vec::new();
+        loop {
+            let line: &str = lines
+                .next()
+                .ok_or_else(|| AgeError::Format(String::from("Stanza body is not terminated")))?;
+            if line.len() > 64 {
+                return Err(AgeError::Format(format!("Stanza body line is too long {line:?}")));
+            }
+            body.extend(decode(line)?);
+            if line.len() < 64 {
+                break;
+            }
+        }
+        stanzas.push(Stanza { args, body });
+    }
+    Ok(stanzas)
+}
+
+fn unwrap_file_key(stanzas: &[&str], identities: &[Identity]) -> Result<Vec<u8>, AgeError> {
+    for stanza in parse_stanzas(stanzas)? {
+        let share: &str = match stanza.args[..] {
+            ["X25519", share] => share,
+            ["X25519", ..] => return Err(AgeError::Format(String::from("Invalid X25519 stanza"))),
+            _ => continue,
+        };
+        let share: Vec<u8> = decode(share)?;
+        if share.len() != 32 || stanza.body.len() != 16 + TAG_SIZE {
+            return Err(AgeError::Format(String::from("Invalid X25519 stanza")));
+        }
+        let share_key = PKey::public_key_from_raw_bytes(&share, Id::X25519)
+            .map_err(|_| AgeError::Format(String::from("Invalid X25519 share")))?;
+        for identity in identities {
+            let shared: Vec<u8> = match derive(&identity.0, &share_key) {
+                Ok(shared) if shared.iter().any(|b| *b != 0) => shared,
+                _ => continue,
+            };
+            let salt: Vec<u8> = [share.as_slice(), &identity.0.raw_public_key()?].concat();
+            let wrap_key: Vec<u8> = hkdf(&salt, &shared, X25519_LABEL.as_bytes())?;
+            match open(&wrap_key, &[0; 12], &stanza.body) {
+                Some(file_key) if file_key.len() == 16 => return Ok(file_key),
+                Some(_) => return Err(AgeError::Format(String::from("Invalid file key length"))),
+                None => {}
+            }
+        }
+    }
+
+    Err(AgeError::Decrypt(String::from("None of the identities can decrypt the file")))
+}
+
+/// Split a file after the line with the header MAC
+fn split_header(file: &[u8]) -> Result<(&str, &[u8]), AgeError> {
+    let mut start: usize = 0;
+    for line in file.split(|b| *b == b'\n') {
+        let end: usize = start + line.len();
+        if line.starts_with(b"--- ") {
+            let header = std::str::from_utf8(&file[..end])
+                .map_err(|_| AgeError::Format(String::from("Header is not valid UTF-8")))?;
+            return Ok((header, file.get(end + 1..).unwrap_or_default()));
+        }
+        start = end + 1;
+    }
+    Err(AgeError::Format(String::from("Missing header")))
+}
+
+fn dearmor(file: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let text: &str = match std::str::from_utf8(file) {
+        Ok(text) if text.trim_start().starts_with(ARMOR_BEGIN) => text.trim(),
+        _ => return Ok(file.to_vec()),
+    };
+    let body: String = text
+        .strip_prefix(ARMOR_BEGIN)
+        .and_then(|text| text.strip_suffix(ARMOR_END))
+        .ok_or_else(|| AgeError::Format(String::from("Invalid armor")))?
+        .split_whitespace()
+        .collect();
+    base64::decode(body).map_err(|e| AgeError::Format(format!("Invalid armor: {e}")))
+}
+
+fn chunk_nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[3..11].copy_from_slice(&counter.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+fn seal(key: &[u8], nonce: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let mut tag = [0u8; TAG_SIZE];
+    let mut ciphertext: Vec<u8> =
+        encrypt_aead(Cipher::chacha20_poly1305(), key, Some(nonce), &[], plaintext, &mut tag)?;
+    ciphertext.extend(tag);
+    Ok(ciphertext)
+}
+
+fn open(key: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    let split: usize = ciphertext.len().checked_sub(TAG_SIZE)?;
+    let (ciphertext, tag) = ciphertext.split_at(split);
+    decrypt_aead(Cipher::chacha20_poly1305(), key, Some(nonce), &[], ciphertext, tag).ok()
+}
+
+fn derive(private: &PKey<Private>, public: &PKey<Public>) -> Result<Vec<u8>, AgeError> {
+    let mut deriver = Deriver::new(private)?;
+    deriver.set_peer(public)?;
+    Ok(deriver.derive_to_vec()?)
+}
+
+/// HKDF with SHA-256 (RFC 5869), producing a 32 byte key
+fn hkdf(salt: &[u8], ikm: &[u8], info: &[u8]) -> Result<Vec<u8>, AgeError> {
+    let prk: Vec<u8> = hmac(salt, ikm)?;
+    hmac(&prk, &[info, &[1]].concat())
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Result<Vec<u8>, AgeError> {
+    // OpenSSL rejects empty keys, which are the same as a key of zeros in HMAC
+    let key = PKey::hmac(if key.is_empty() { &[0; 32] } else { key })?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data)?;
+    Ok(signer.sign_to_vec()?)
+}
+
+fn random(len: usize) -> Result<Vec<u8>, AgeError> {
+    let mut bytes: Vec<u8> = vec![0; len];
+    openssl::rand::rand_bytes(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn encode(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::STANDARD_NO_PAD)
+}
+
+/// Decode unpadded base64, rejecting any other encoding of the same bytes as the specification requires
+fn decode(text: &str) -> Result<Vec<u8>, AgeError> {
+    let bytes: Vec<u8> = base64::decode_config(text, base64::STANDARD_NO_PAD)
+        .map_err(|e| AgeError::Format(format!("Invalid base64 {text:?}: {e}")))?;
+    match encode(&bytes) == text {
+        true => Ok(bytes),
+        false => Err(AgeError::Format(format!("Non-canonical base64 {text:?}"))),
+    }
+}
+
+fn bech32_encode(hrp: &str, bytes: &[u8]) -> String {
+    let data: Vec<u8> = convert_bits(bytes, 8, 5, true).unwrap();
+    let mut values: Vec<u8> = hrp_expand(hrp);
+    values.extend(&data);
+    values.extend([0; 6]);
+    let checksum: u32 = polymod(&values) ^ 1;
+    let checksum = (0..6).map(|i| ((checksum >> (5 * (5 - i))) & 31) as u8);
+
+    let data: String = data
+        .into_iter()
+        .chain(checksum)
+        .map(|v| BECH32_CHARSET[v as usize] as char)
+        .collect();
+    format!("{hrp}1{data}")
+}
+
+fn bech32_decode(hrp: &str, key: &str) -> Result<Vec<u8>, AgeError> {
+    let invalid =
+        || AgeError::Key(format!("{key} is not a valid {} key", hrp.trim_end_matches('-')));
+    if key.to_lowercase() != key && key.to_uppercase() != key {
+        return Err(invalid());
+    }
+    let key: String = key.to_lowercase();
+    let (prefix, data) = key.rsplit_once('1').ok_or_else(invalid)?;
+    if prefix != hrp || data.len() < 6 {
+        return Err(invalid());
+    }
+
+    let values: Vec<u8> = data
+        .bytes()
+        .map(|c| BECH32_CHARSET.iter().position(|x| *x == c).map(|v| v as u8))
+        .collect::<Option<_>>()
+        .ok_or_else(invalid)?;
+    if polymod(&[hrp_expand(hrp), values.clone()].concat()) != 1 {
+        return Err(invalid());
+    }
+    convert_bits(&values[..values.len() - 6], 5, 8, false).ok_or_else(invalid)
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let mut values: Vec<u8> = hrp.bytes().map(|b| b >> 5).collect();
+    values.push(0);
+    values.extend(hrp.bytes().map(|b| b & 31));
+    values
+}
+
+fn polymod(values: &[u8]) -> u32 {
+    const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+    values.iter().fold(1, |checksum, value| {
+        let top: u32 = checksum >> 25;
+        let checksum: u32 = ((checksum & 0x1ffffff) << 5) ^ u32::from(*value);
+        (0..5).filter(|i| (top >> i) & 1 == 1).fold(checksum, |c, i| c ^ GENERATOR[i])
+    })
+}
+
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max: u32 = (1 << to) - 1;
+    let mut output: Vec<u8> = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for value in data {
+        acc = (acc << from) | u32::from(*value);
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            output.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad && bits > 0 {
+        output.push(((acc << (to - bits)) & max) as u8);
+    } else if !pad && (bits >= from || (acc << (to - bits)) & max != 0) {
+        return None;
+    }
+    Some(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use openssl::pkey::{Id, PKey};
+
+    use super::{
+        chunk_nonce, decrypt, encrypt, encrypt_with, hkdf, seal, split_header, Identity, Recipient,
+        CHUNK_SIZE, TAG_SIZE,
+    };
+
+    // A file encrypted with an independent implementation of the age specification, from fixed
+    // keys: the identity is the bytes 1 to 32, the ephemeral key the bytes 33 to 64, the file key
+    // the bytes 100 to 115 and the payload nonce the bytes 200 to 215
+    const IDENTITY: &str =
+        "AGE-SECRET-KEY-1QYPQXPQ9QCRSSZG2PVXQ6RS0ZQG3YYC5Z5TPWXQERGD3C8G7RUSQGPQYEE";
+    const RECIPIENT: &str = "age1q73he0q5yzfu3d64msd3p6rvksnrwjk3d2598mgtmlqt9wrdr37q2vrn72";
+    const FILE: &str = "YWdlLWVuY3J5cHRpb24ub3JnL3YxCi0+IFgyNTUxOSBXR212OUZCVWx6TExxdTFlWGZtekNtMmpITERsZEN1dFd0U2hwMmp4cG5zCmoxUmpXV1BrVk5YZHJFQXhlOXFVeERFK05wZGF1aytLWmNXSUhoVDFENUkKLS0tIDBJUjYrc1ZiNUFMaENUbTdIcnBMWFlwOTM1U0srOXBMWkZqckloOERUNGMKyMnKy8zNzs/Q0dLT1NXW1827ODi6V54C3ZyTMYlzHeBrrHM2harBZd+jZiQk";
+
+    #[test]
+    fn test_interoperability() {
+        let identity = Identity::parse(IDENTITY).unwrap();
+        assert_eq!(RECIPIENT, identity.recipient().unwrap().to_string());
+        let file: Vec<u8> = base64::decode(FILE).unwrap();
+        assert_eq!(b"TOKEN=secret\n".to_vec(), decrypt(&file, &[identity]).unwrap());
+
+        let bytes = |range: std::ops::Range<u8>| range.collect::<Vec<u8>>();
+        let ephemeral = PKey::private_key_from_raw_bytes(&bytes(33..65), Id::X25519).unwrap();
+        let recipients = vec![Recipient::parse(RECIPIENT).unwrap()];
+        let encrypted = encrypt_with(
+            b"TOKEN=secret\n",
+            &recipients,
+            &bytes(100..116),
+            &bytes(200..216),
+            &[ephemeral],
+        )
+        .unwrap();
+        assert_eq!(file, encrypted);
+    }
+
+    #[test]
+    fn test_reject_invalid_files() {
+        let identities = vec![Identity::parse(IDENTITY).unwrap()];
+        let file: Vec<u8> = base64::decode(FILE).unwrap();
+        let end: usize = file.windows(4).position(|w| w == b"\n---").unwrap() + 5;
+        let header_end: usize = end + file[end..].iter().position(|b| *b == b'\n').unwrap();
+
+        let truncated_mac: Vec<u8> = [&file[..end + 10], &file[header_end..]].concat();
+        assert!(decrypt(&truncated_mac, &identities).is_err());
+        let only_nonce: Vec<u8> = file[..header_end + 1 + 16].to_vec();
+        assert!(decrypt(&only_nonce, &identities).is_err());
+    }
+
+    #[test]
+    fn test_reject_malformed_stanzas() {
+        let identities = vec![Identity::parse(IDENTITY).unwrap()];
+        let file: Vec<u8> = base64::decode(FILE).unwrap();
+        let (header, payload) = split_header(&file).unwrap();
+        let replaced = |from: &str, to: &str| -> Vec<u8> {
+            assert!(header.contains(from));
+            [header.replacen(from, to, 1).as_bytes(), b"\n", payload].concat()
+        };
+        assert!(decrypt(&replaced("X25519", "X25519"), &identities).is_ok());
+
+        let share: &str = "WGmv9FBUlzLLqu1eXfmzCm2jHLDldCutWtShp2jxpns";
+        let body: &str = "j1RjWWPkVNXdrEAxe9qUxDE+Npdauk+KZcWIHhT1D5I";
+        let malformed: Vec<(&str, String)> = vec![
+            (share, format!("{share} extra")),
+            ("-> X25519 ", String::from("-> X25519  ")),
+            (share, share[4..].to_string()),
+            (body, format!("{}J", &body[..body.len() - 1])),
+            (body, format!("{body}=")),
+            (body, body[8..].to_string()),
+            (body, "A".repeat(64)),
+            (body, "A".repeat(68)),
+        ];
+        for (from, to) in malformed {
+            assert!(decrypt(&replaced(from, &to), &identities).is_err(), "{to}");
+        }
+    }
+
+    #[test]
+    fn test_reject_invalid_file_keys_and_chunks() {
+        let identities = vec![Identity::parse(IDENTITY).unwrap()];
+        let recipients = vec![Recipient::parse(RECIPIENT).unwrap()];
+        let bytes = |range: std::ops::Range<u8>| range.collect::<Vec<u8>>();
+        let ephemeral = || PKey::private_key_from_raw_bytes(&bytes(33..65), Id::X25519).unwrap();
+        let (nonce, file_key) = (bytes(200..216), bytes(100..116));
+
+        for file_key in [bytes(100..115), bytes(100..117)] {
+            let file = encrypt_with(b"", &recipients, &file_key, &nonce, &[ephemeral()]).unwrap();
+            assert!(decrypt(&file, &identities).is_err());
+        }
+
+        let empty: Vec<u8> =
+            encrypt_with(b"", &recipients, &file_key, &nonce, &[ephemeral()]).unwrap();
+        let header: &[u8] = &empty[..empty.len() - TAG_SIZE];
+        let payload_key: Vec<u8> = hkdf(&nonce, &file_key, b"payload").unwrap();
+        let chunk = |counter: u64, last: bool, plaintext: &[u8]| -> Vec<u8> {
+            seal(&payload_key, &chunk_nonce(counter, last), plaintext).unwrap()
+        };
+        let full: Vec<u8> = vec![7; CHUNK_SIZE];
+
+        let valid: Vec<u8> = [header, &chunk(0, false, &full), &chunk(1, true, b"7")].concat();
+        assert!(decrypt(&valid, &identities).is_ok());
+        let empty_final: Vec<u8> = [header, &chunk(0, false, &full), &chunk(1, true, b"")].concat();
+        assert!(decrypt(&empty_final, &identities).is_err());
+        let empty_first: Vec<u8> = [header, &chunk(0, false, b""), &chunk(1, true, b"7")].concat();
+        assert!(decrypt(&empty_first, &identities).is_err());
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let identity = Identity::generate().unwrap();
+        let key: String = identity.to_string();
+        assert!(key.starts_with("AGE-SECRET-KEY-1"));
+        let recipient: String = identity.recipient().unwrap().to_string();
+        assert!(recipient.starts_with("age1"));
+
+        let identities =
+            Identity::parse_file(&format!("# public key: {recipient}\n{key}\n")).unwrap();
+        let recipients = vec![Recipient::parse(&recipient).unwrap()];
+        for plaintext in [&b""[..], b"TOKEN=secret\n", &vec![7u8; 64 * 1024 + 1]] {
+            let file: Vec<u8> = encrypt(plaintext, &recipients).unwrap();
+            assert!(file.starts_with(b"age-encryption.org/v1\n-> X25519 "));
+            assert_eq!(plaintext, decrypt(&file, &identities).unwrap());
+        }
+
+        let mut tampered: Vec<u8> = encrypt(b"TOKEN=secret\n", &recipients).unwrap();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&tampered, &identities).is_err());
+        let other = Identity::generate().unwrap();
+        assert!(decrypt(&encrypt(b"", &recipients).unwrap(), &[other]).is_err());
+        assert!(Recipient::parse("age1invalid").is_err());
+    }
+}
@@ -1,20 +1,23 @@
 use std::{
+    collections::HashSet,
     path::{Path, PathBuf},
     time::Duration,
 };
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use git2::{Repository, RepositoryOpenFlags};
-use termcolor::ColorChoice;
+use termcolor::{Color, ColorChoice, StandardStream};
 use walkdir::WalkDir;
 
-use crate::prop::{self, ParsePropertyError, Property};
+use crate::prop::{self, Environment, ParsePropertyError, Property};
+use crate::{dotenv, io, prompt, runner, secrets, template};
 
 const BANNER: &str = include_str!("../resources/banner");
 const ABOUT: &str = include_str!("../resources/about");
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = ABOUT, before_long_help = BANNER)]
+#[clap(subcommand_negates_reqs = true)]
 pub struct Args {
     /// Set verbosity level, 0 - 5
     ///
@@ -57,13 +60,37 @@ pub struct Args {
     #[clap(short, long)]
     request: bool,
 
+    /// Print as curl command
+    ///
+    /// Print the request as a curl command, with all variables substituted and default headers
+    /// added, instead of sending it
+    #[clap(long)]
+    curl: bool,
+
+    /// Redact secrets
+    ///
+    /// Replace values of variables from `.sec` files and values of sensitive headers with `***`
+    /// when printing the request as a curl command
+    #[clap(long, requires = "curl")]
+    redact: bool,
+
+    /// Show secrets
+    ///
+    /// Show secret values in all output, logs and reports. By default values of variables from
+    /// `.sec` files that are used by the request, values given when prompted for secrets and
+    /// values of sensitive headers like `Authorization`, `Cookie` and `X-Api-Key` are replaced with
+    /// `***` wherever they occur. Curl commands are only redacted with `--redact`.
+    #[clap(long = "show-secrets", conflicts_with = "redact")]
+    show_secrets: bool,
+
     /// Environments
     ///
     /// One or several environments which containins environment variables. If the environment is
     /// `development`, the application will search for any occurence of `development.env` or
     /// `development.sec` in the current directory and parent directories, as long as the search is
     /// confined to the Git repository where the request resides. If the command is not executed
-    /// inside a Git repository, no traversing to parental directories will be done.
+    /// inside a Git repository, no traversing to parental directories will be done. Encrypted files
+    /// like `development.sec.age` are decrypted and used like `development.sec`.
     ///
     /// Varaibles found in *.env or *.sec files will override the environment variables inherited
     /// from the operating system and in the special `.env`/`.sec` which is a "global" environment
@@ -81,17 +108,275 @@ pub struct Args {
     #[clap(short = 'E', long = "variable")]
     arg_vars: Vec<Property>,
 
+    /// Request name
+    ///
+    /// Name of the request to execute, when the request file contains more than one request. A
+    /// request without a name can be selected by its position in the file, starting from 1.
+    #[clap(short = 'n', long = "name")]
+    name: Option<String>,
+
+    /// List requests
+    ///
+    /// List the name and description of each request in the request file, without executing any
+    /// of them
+    #[clap(short = 'l', long = "list")]
+    list: bool,
+
     /// Request timeout
     ///
     /// Max time to wait, in seconds, before request times out
     #[clap(short = 'T', long = "timeout", default_value = "30")]
     timeout: usize,
 
+    /// JUnit report
+    ///
+    /// Write a JUnit XML report of the result of each request to this file, when executing all
+    /// requests in a directory or matching a glob pattern
+    #[clap(long = "junit")]
+    junit: Option<PathBuf>,
+
+    /// TAP report
+    ///
+    /// Write a report in the Test Anything Protocol (TAP) format of the result of each request to
+    /// this file, when executing all requests in a directory or matching a glob pattern
+    #[clap(long = "tap")]
+    tap: Option<PathBuf>,
+
+    /// OpenAPI document
+    ///
+    /// Validate each response against the operation in this OpenAPI 3 document that matches the
+    /// method and path of the request. This overrides an `openapi` document given in the request
+    /// file.
+    #[clap(long = "openapi")]
+    openapi: Option<PathBuf>,
+
+    /// HAR file
+    ///
+    /// Record each executed request and its response, with timing, to this file in the HTTP
+    /// Archive (HAR) format
+    #[clap(long = "har")]
+    har: Option<PathBuf>,
+
+    /// Allow commands and files in templates
+    ///
+    /// Enable the `exec` and `file` template helpers, which run a shell command or read a file and
+    /// insert its output or content, like `{{exec "gcloud auth print-access-token"}}`. Only use this
+    /// with request files that you trust.
+    #[clap(long = "allow-exec")]
+    allow_exec: bool,
+
+    /// Never prompt
+    ///
+    /// Fail instead of prompting for values of variables that are missing, which otherwise is done
+    /// when running in an interactive terminal
+    #[clap(long = "no-prompt")]
+    no_prompt: bool,
+
     /// Request file
     ///
-    /// Request template file which contains the request that should be executed
-    #[clap(value_parser)]
-    file: PathBuf,
+    /// Request template file which contains the request that should be executed. If this is a
+    /// directory or a (quoted) glob pattern like `'api/**/*.yml'`, all requests in all files found
+    /// in it or matching it are executed in alphabetical order.
+    #[clap(value_parser, required = true)]
+    file: Option<PathBuf>,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Import requests from other formats into request files
+    #[clap(subcommand)]
+    Import(Import),
+
+    /// Show the variables used by a request
+    ///
+    /// Print each variable used by the request in a request file, with the value it resolves to and
+    /// where that value comes from, followed by every definition it overrides. Values from `.sec`
+    /// files are masked. Variables that are defined in environment files or with `--variable`, but
+    /// never used, are listed as warnings. Options like `--env`, `--variable` and `--name` must be
+    /// given before `env`.
+    Env {
+        /// The request file
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+
+    /// Manage encrypted environment files
+    #[clap(subcommand)]
+    Secrets(Secrets),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Secrets {
+    /// Encrypt an environment file
+    ///
+    /// Encrypt an environment file, like `staging.sec`, with age to a file with the same name
+    /// ending with `.age`, like `staging.sec.age`, which can be committed. The original file is
+    /// kept, and should be removed or ignored by Git.
+    Encrypt {
+        #[clap(flatten)]
+        keys: Keys,
+
+        /// Overwrite an existing encrypted file
+        #[clap(short, long)]
+        force: bool,
+
+        /// The environment file to encrypt
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+
+    /// Edit an encrypted environment file
+    ///
+    /// Decrypt an environment file encrypted with age, like `staging.sec.age`, to a temporary file
+    /// that is opened in the editor in `VISUAL` or `EDITOR`, and encrypt it again when the editor is
+    /// closed. The file is created if it does not exist.
+    Edit {
+        #[clap(flatten)]
+        keys: Keys,
+
+        /// The encrypted environment file
+        #[clap(value_parser)]
+        file: PathBuf,
+    },
+
+    /// Create an identity
+    ///
+    /// Create a new identity to decrypt files with, and print its public key, which others can
+    /// encrypt files to
+    Keygen {
+        /// Write the identity to this file, instead of `FIRE_IDENTITY` or
+        /// `~/.config/fire/identity.txt`
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+
+        /// Overwrite an existing identity
+        #[clap(short, long)]
+        force: bool,
+    },
+}
+
+/// Keys used to decrypt and encrypt environment files
+#[derive(clap::Args, Debug)]
+pub struct Keys {
+    /// Identity file to decrypt with, instead of `FIRE_IDENTITY` or `~/.config/fire/identity.txt`
+    #[clap(short, long)]
+    pub identity: Option<PathBuf>,
+
+    /// Public key of a recipient to encrypt to, like `age1...`, instead of the keys in the closest
+    /// `.recipients` file or the key of the identity
+    #[clap(short, long)]
+    pub recipient: Vec<String>,
+
+    /// File with public keys of recipients to encrypt to, one per line
+    #[clap(short = 'R', long)]
+    pub recipients_file: Vec<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Import {
+    /// Import a curl command
+    ///
+    /// Convert a curl command, such as one copied from the developer tools of a browser, into a
+    /// request file. The command is read from stdin if it is not given as an argument. Any options
+    /// to this command must be given before the curl command.
+    #[clap(trailing_var_arg = true)]
+    Curl {
+        /// Write the request file to this path instead of stdout
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+
+        /// The curl command, either quoted as a single argument or as separate arguments
+        #[clap(value_parser)]
+        command: Vec<String>,
+    },
+
+    /// Import a Postman collection
+    ///
+    /// Convert a collection in the Postman v2.1 format into request files, with a directory for
+    /// each folder in the collection. Collection variables are written to `.env`, and the
+    /// variables of each environment to `<environment>.env`, or `<environment>.sec` for secrets.
+    Postman {
+        /// Directory to write the request files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// An environment exported from Postman, to convert into environment files
+        #[clap(short, long)]
+        environment: Vec<PathBuf>,
+
+        /// Overwrite existing files
+        #[clap(short, long)]
+        force: bool,
+
+        /// The exported collection
+        #[clap(value_parser)]
+        collection: PathBuf,
+    },
+
+    /// Import an Insomnia export
+    ///
+    /// Convert an export from Insomnia into request files, with a directory for each folder. The
+    /// variables of the base environment are written to `.env`, and the variables of each sub
+    /// environment to `<environment>.env`.
+    Insomnia {
+        /// Directory to write the request files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Overwrite existing files
+        #[clap(short, long)]
+        force: bool,
+
+        /// The exported file, in JSON format
+        #[clap(value_parser)]
+        export: PathBuf,
+    },
+
+    /// Import a HAR file
+    ///
+    /// Convert the entries of an HTTP Archive (HAR), such as one saved from the developer tools of
+    /// a browser, into request files, with a directory for each host.
+    Har {
+        /// Directory to write the request files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Only import requests with a URL containing this text
+        #[clap(short = 'u', long)]
+        filter: Option<String>,
+
+        /// Overwrite existing files
+        #[clap(short, long)]
+        force: bool,
+
+        /// The HAR file
+        #[clap(value_parser)]
+        har: PathBuf,
+    },
+
+    /// Generate request files from an OpenAPI document
+    ///
+    /// Generate a request file for each operation in an OpenAPI 3 document, with a directory for
+    /// the first tag of each operation. Path parameters, and required query parameters and
+    /// headers, are variables in the request files. The URL of the first server is written to
+    /// `.env` as the variable `base_url`.
+    #[clap(name = "openapi")]
+    OpenApi {
+        /// Directory to write the request files to
+        #[clap(short, long, default_value = ".")]
+        output: PathBuf,
+
+        /// Overwrite existing files
+        #[clap(short, long)]
+        force: bool,
+
+        /// The OpenAPI document, in YAML or JSON
+        #[clap(value_parser)]
+        spec: PathBuf,
+    },
 }
 
 impl Args {
@@ -107,7 +392,15 @@ impl Args {
     }
 
     pub fn file(&self) -> &std::path::Path {
-        &self.file
+        match (&self.file, &self.command) {
+            (Some(file), _) => file,
+            (None, Some(Command::Env { file })) => file,
+            (None, _) => panic!("Request file is required without a subcommand"),
+        }
+    }
+
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
     }
 
     pub fn timeout(&self) -> Duration {
@@ -118,6 +411,57 @@ impl Args {
         self.request
     }
 
+    pub fn curl(&self) -> bool {
+        self.curl
+    }
+
+    pub fn redact(&self) -> bool {
+        self.redact
+    }
+
+    pub fn show_secrets(&self) -> bool {
+        self.show_secrets
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn list(&self) -> bool {
+        self.list
+    }
+
+    pub fn junit(&self) -> Option<&Path> {
+        self.junit.as_deref()
+    }
+
+    pub fn tap(&self) -> Option<&Path> {
+        self.tap.as_deref()
+    }
+
+    pub fn openapi(&self) -> Option<&Path> {
+        self.openapi.as_deref()
+    }
+
+    pub fn har(&self) -> Option<&Path> {
+        self.har.as_deref()
+    }
+
+    pub fn allow_exec(&self) -> bool {
+        self.allow_exec
+    }
+
+    /// Whether to prompt for values of variables that are missing
+    pub fn prompt(&self) -> bool {
+        !self.no_prompt && prompt::is_interactive()
+    }
+
+    /// Whether the request file argument refers to a collection of request files, by being a
+    /// directory or a glob pattern
+    pub fn is_collection(&self) -> bool {
+        self.file().is_dir() || runner::is_glob(self.file())
+    }
+
     pub fn env(&self) -> Result<Vec<Property>, ParsePropertyError> {
         let sys_envs: Vec<Property> = Self::read_sys_envs()?;
         let file_envs: Vec<Property> = self.read_file_envs()?;
@@ -135,7 +479,6 @@ impl Args {
 
     fn read_sys_envs() -> Result<Vec<Property>, ParsePropertyError> {
         std::env::vars()
-            .into_iter()
             .map(Property::try_from)
             .map(|res| res.map(|prop| prop.with_source(prop::Source::EnvVar)))
             .collect()
@@ -143,12 +486,90 @@ impl Args {
 
     fn read_file_envs(&self) -> Result<Vec<Property>, ParsePropertyError> {
         let file_envs: Result<Vec<Vec<Property>>, ParsePropertyError> =
-            Self::find_env_files(&self.file, self.env.clone())
+            Self::find_env_files(&self.search_dir(), self.env.clone())
                 .into_iter()
-                .map(|file| prop::from_file(&file))
+                .map(|file| {
+                    let environment: Option<Environment> = self.environment(&file);
+                    prop::from_file(&file, environment)
+                })
                 .collect();
 
-        file_envs.map(|vec| vec.into_iter().flatten().collect())
+        let props: Vec<Property> = file_envs?.into_iter().flatten().collect();
+        self.warn_conflicts(&props);
+        Ok(props)
+    }
+
+    /// The environment given with `--env` that an environment file belongs to, which is `None` for
+    /// the global `.env` and `.sec` files. An environment given several times has the position of
+    /// its last occurrence.
+    fn environment(&self, file: &Path) -> Option<Environment> {
+        let name: String = file.file_name()?.to_string_lossy().to_string();
+        let name: &str = secrets::plain_name(&name);
+        let name: &str = name.strip_suffix(".env").or_else(|| name.strip_suffix(".sec"))?;
+        let position: usize = self.env.iter().rposition(|env| env == name)?;
+        Some(Environment {
+            position: position + 1,
+            name: name.to_string(),
+        })
+    }
+
+    /// Warn about keys that are defined by several environments in the same directory, where only
+    /// the environment given last is used. The warning is always written, regardless of verbosity.
+    fn warn_conflicts(&self, props: &[Property]) {
+        let mut stderr = StandardStream::stderr(self.use_colors());
+        let mut seen: Vec<(&Path, &str, &Environment)> = Vec::new();
+        for prop in props {
+            let (dir, env) = match (prop.origin().and_then(Path::parent), prop.source()) {
+                (Some(dir), prop::Source::File(_, Some(env))) => (dir, env),
+                _ => continue,
+            };
+            let conflict = seen
+                .iter()
+                .find(|(d, key, e)| *d == dir && *key == prop.key() && e.name != env.name);
+            // Both the `.env` and `.sec` file of an environment may define the key, but the conflict
+            // with another environment is only reported for the first of them
+            let reported: bool = seen
+                .iter()
+                .any(|(d, key, e)| *d == dir && *key == prop.key() && e.name == env.name);
+            if let Some((_, _, other)) = conflict.filter(|_| !reported) {
+                let used: &str =
+                    if env.position > other.position { &env.name } else { &other.name };
+                let message: String = format!(
+                    "warning: {} is defined by both environment {} and {} in {:?}, the value from {used} is used",
+                    prop.key(),
+                    other.name,
+                    env.name,
+                    dir
+                );
+                io::writeln_color(&mut stderr, &message, Some(Color::Yellow));
+            }
+            seen.push((dir, prop.key(), env));
+        }
+    }
+
+    /// Root directory of the project that the request file belongs to, which is the root of the
+    /// Git repository if there is one, or otherwise the directory of the request file
+    pub fn project_root(&self) -> PathBuf {
+        match Self::git_root() {
+            Some(root) => root.parent().unwrap().to_path_buf(),
+            None => self.search_dir(),
+        }
+    }
+
+    /// The directory where the search for environment files ends, which is the directory of the
+    /// request file, or the directory of the collection of request files. A collection that does
+    /// not exist is reported by [`runner::request_files`], so its path is used as it is.
+    fn search_dir(&self) -> PathBuf {
+        let file: &Path = self.file();
+        if runner::is_glob(file) {
+            let base: PathBuf = runner::glob_base(file);
+            let base: &Path = if base.as_os_str().is_empty() { Path::new(".") } else { &base };
+            base.canonicalize().unwrap_or_else(|_| base.to_path_buf())
+        } else if file.is_dir() {
+            file.canonicalize().unwrap_or_else(|_| file.to_path_buf())
+        } else {
+            file.canonicalize().unwrap().parent().unwrap().to_path_buf()
+        }
     }
 
     fn read_arg_vars(&self) -> Vec<Property> {
@@ -159,36 +580,66 @@ impl Args {
             .collect()
     }
 
-    fn find_env_files(request_file: &Path, environments: Vec<String>) -> Vec<PathBuf> {
+    /// Names of all variables in any `.sec` file, in any environment, which are considered secret.
+    /// Only the names are used, and encrypted files are not decrypted.
+    pub fn secret_names(&self) -> HashSet<String> {
+        Self::find_files(&self.search_dir(), |name| name.ends_with(".sec"))
+            .iter()
+            .filter_map(|file| std::fs::read_to_string(file).ok())
+            .filter_map(|content| dotenv::parse(&content).ok())
+            .flatten()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /// All `_partials` directories in the directory of the request file or any parent directory up
+    /// to the root of the Git repository, ordered from the outermost directory
+    pub fn partial_dirs(&self) -> Vec<PathBuf> {
+        Self::search_dirs(&self.search_dir())
+            .into_iter()
+            .map(|dir| dir.join(template::PARTIALS_DIR))
+            .filter(|dir| dir.is_dir())
+            .collect()
+    }
+
+    fn find_env_files(dir: &Path, environments: Vec<String>) -> Vec<PathBuf> {
         let mut files: Vec<String> = environments
             .into_iter()
-            .map(|env| vec![env.clone() + ".env", env + ".sec"])
-            .flatten()
+            .flat_map(|env| vec![env.clone() + ".env", env + ".sec"])
             .collect();
 
         files.push(String::from(".env"));
         files.push(String::from(".sec"));
 
-        let end: PathBuf = request_file.canonicalize().unwrap().parent().unwrap().to_path_buf();
+        let encrypted: Vec<String> = files
+            .iter()
+            .filter(|file| file.ends_with(".sec"))
+            .flat_map(|file| secrets::encrypted_names(file))
+            .collect();
+        files.extend(encrypted);
 
-        let start: PathBuf = match Self::git_root() {
-            Some(root) => root.parent().unwrap().to_path_buf(),
-            None => end.clone(),
-        };
+        Self::find_files(dir, |name| files.iter().any(|file| file == name))
+    }
+
+    /// Files with a name accepted by `filter`, in `dir` or any parent directory up to the root of
+    /// the Git repository
+    fn find_files(dir: &Path, filter: impl Fn(&str) -> bool) -> Vec<PathBuf> {
+        let end: PathBuf = dir.to_path_buf();
+        let start: PathBuf = Self::search_start(&end);
 
         log::info!("Start is {:?}", start);
         log::info!("End is {:?}", end);
         WalkDir::new(start)
             .follow_links(false)
             .contents_first(false)
+            .sort_by_file_name()
             .into_iter()
             .filter_entry(|entry| end.starts_with(entry.path()) || entry.file_type().is_file())
             .filter_map(|entry| entry.ok())
             .filter(|entry| {
                 let ftype = entry.file_type();
                 if ftype.is_file() {
-                    let name: String = entry.file_name().to_str().unwrap().to_string();
-                    files.contains(&name)
+                    filter(&entry.file_name().to_string_lossy())
                 } else {
                     false
                 }
@@ -198,6 +649,27 @@ impl Args {
             .collect()
     }
 
+    /// The directory where the search for files ends in `dir` starts, which is the root of the Git
+    /// repository, or `dir` itself outside of a Git repository
+    fn search_start(dir: &Path) -> PathBuf {
+        match Self::git_root() {
+            Some(root) => root.parent().unwrap().to_path_buf(),
+            None => dir.to_path_buf(),
+        }
+    }
+
+    /// The directories searched for files ending in `dir`, from the outermost directory to `dir`
+    fn search_dirs(dir: &Path) -> Vec<PathBuf> {
+        let start: PathBuf = Self::search_start(dir);
+        let mut dirs: Vec<PathBuf> = dir
+            .ancestors()
+            .take_while(|d| d.starts_with(&start))
+            .map(Path::to_path_buf)
+            .collect();
+        dirs.reverse();
+        dirs
+    }
+
     fn git_root() -> Option<PathBuf> {
         let ceiling = ["/"];
         Repository::open_ext(".", RepositoryOpenFlags::CROSS_FS, ceiling)
@@ -0,0 +1,217 @@
+use std::fmt::Display;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use reqwest::blocking::multipart::{Form, Part as FormPart};
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::params;
+
+/// Parts of a `multipart/form-data` body, in the order they are declared in the request file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Mapping")]
+pub struct Multipart(Vec<(String, Part)>);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Part {
+    value: Option<String>,
+    file: Option<PathBuf>,
+    filename: Option<String>,
+    content_type: Option<String>,
+}
+
+/// A body that is read from a file, or from stdin if the path is `-`. It is either given as only
+/// a path, or with an explicit flag for whether the content of the file should be rendered as a
+/// template.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BodyFile {
+    Path(PathBuf),
+    Options {
+        path: PathBuf,
+        #[serde(default)]
+        template: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum BodyError {
+    File(PathBuf, std::io::Error),
+    ContentType(String),
+    Encoding(PathBuf),
+}
+
+const STDIN: &str = "-";
+
+impl BodyFile {
+    pub fn path(&self) -> &Path {
+        match self {
+            BodyFile::Path(path) => path,
+            BodyFile::Options { path, .. } => path,
+        }
+    }
+
+    pub fn template(&self) -> bool {
+        match self {
+            BodyFile::Path(_) => false,
+            BodyFile::Options { template, .. } => *template,
+        }
+    }
+
+    /// Read the content of the body file, resolving the path relative to the directory `base`.
+    pub fn read(&self, base: &Path) -> Result<Vec<u8>, BodyError> {
+        let mut content: Vec<u8> = Vec::new();
+        if self.path() == Path::new(STDIN) {
+            std::io::stdin()
+                .read_to_end(&mut content)
+                .map_err(|e| BodyError::File(self.path().to_path_buf(), e))?;
+        } else {
+            let path: PathBuf = base.join(self.path());
+            content = std::fs::read(&path).map_err(|e| BodyError::File(path, e))?;
+        }
+        Ok(content)
+    }
+}
+
+impl Multipart {
+    /// Build the form that is sent, resolving files relative to the directory `base`, which
+    /// should be the directory of the request file.
+    pub fn form(&self, base: &Path) -> Result<Form, BodyError> {
+        self.0.iter().try_fold(Form::new(), |form, (name, part)| {
+            Ok(form.part(name.clone(), part.build(base)?))
+        })
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, Part)> {
+        self.0.iter()
+    }
+}
+
+impl TryFrom<Mapping> for Multipart {
+    type Error = String;
+
+    fn try_from(mapping: Mapping) -> Result<Self, Self::Error> {
+        let mut parts: Vec<(String, Part)> = Vec::with_capacity(mapping.len());
+        for (name, value) in mapping {
+            let name: String = params::scalar(name)?;
+            let part: Part = match value {
+                Value::Mapping(_) => serde_yaml::from_value(value).map_err(|e| e.to_string())?,
+                value => Part::text(params::scalar(value)?),
+            };
+            if part.value.is_some() == part.file.is_some() {
+                return Err(format!("Part `{name}` must have exactly one of `value` or `file`"));
+            }
+            parts.push((name, part));
+        }
+        Ok(Multipart(parts))
+    }
+}
+
+impl Part {
+    fn text(value: String) -> Part {
+        Part {
+            value: Some(value),
+            file: None,
+            filename: None,
+            content_type: None,
+        }
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
+
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    fn build(&self, base: &Path) -> Result<FormPart, BodyError> {
+        let part: FormPart = match (&self.value, &self.file) {
+            (Some(value), _) => FormPart::text(value.clone()),
+            (None, Some(file)) => {
+                let path: PathBuf = base.join(file);
+                FormPart::file(&path).map_err(|e| BodyError::File(path, e))?
+            }
+            (None, None) => FormPart::text(""),
+        };
+
+        let part: FormPart = match &self.filename {
+            Some(filename) => part.file_name(filename.clone()),
+            None => part,
+        };
+
+        match &self.content_type {
+            Some(ct) => part.mime_str(ct).map_err(|_| BodyError::ContentType(ct.clone())),
+            None => Ok(part),
+        }
+    }
+}
+
+impl Display for Part {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.value, &self.file) {
+            (Some(value), _) => write!(f, "{value}")?,
+            (None, Some(file)) => write!(f, "@{}", file.display())?,
+            (None, None) => {}
+        }
+        if let Some(filename) = &self.filename {
+            write!(f, " (filename: {filename})")?;
+        }
+        if let Some(ct) = &self.content_type {
+            write!(f, " ({ct})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{BodyFile, Multipart};
+
+    #[test]
+    fn test_parse_body_file() {
+        let path: BodyFile = serde_yaml::from_str("fixtures/order.json").unwrap();
+        let options: BodyFile = serde_yaml::from_str("path: '-'\ntemplate: true").unwrap();
+
+        assert_eq!(Path::new("fixtures/order.json"), path.path());
+        assert!(!path.template());
+        assert_eq!(Path::new("-"), options.path());
+        assert!(options.template());
+    }
+
+    #[test]
+    fn test_parse_multipart_parts() {
+        let input = r###"
+            description: A cat
+            count: 2
+            avatar:
+              file: images/cat.png
+              filename: avatar.png
+              content_type: image/png
+        "###;
+
+        let multipart: Multipart = serde_yaml::from_str(input).unwrap();
+        let parts: Vec<String> = multipart.iter().map(|(k, v)| format!("{k}={v}")).collect();
+
+        assert_eq!(
+            vec![
+                "description=A cat",
+                "count=2",
+                "avatar=@images/cat.png (filename: avatar.png) (image/png)"
+            ],
+            parts
+        );
+        assert!(serde_yaml::from_str::<Multipart>("bad:\n  filename: x").is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::jsonpath;
+use crate::prop::{Property, Source};
+use crate::redact;
+
+/// Prefix of the keys of captured values, when used as properties in templates
+pub const PREFIX: &str = "captured.";
+
+const STATE_DIR: &str = ".fire";
+const STATE_FILE: &str = "captured.json";
+
+/// Extracts a value from a response.
+///
+/// The value is taken from the header `header`, the JSON body at the JSONPath `json`, or otherwise
+/// the whole body, and only one of `header` and `json` can be set. If `regex` is set, it is applied
+/// to that value and the first capture group (or the whole match, if the regex has no groups) is
+/// what is captured.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawCapture")]
+pub struct Capture {
+    json: Option<String>,
+    header: Option<String>,
+    regex: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct RawCapture {
+    json: Option<String>,
+    header: Option<String>,
+    regex: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    JsonPath(String),
+    Regex(String),
+    State(PathBuf, String),
+}
+
+impl TryFrom<RawCapture> for Capture {
+    type Error = String;
+
+    fn try_from(raw: RawCapture) -> Result<Self, Self::Error> {
+        match (&raw.header, &raw.json) {
+            (Some(header), Some(path)) => Err(format!(
+                "A capture can use either header {header} or JSON path {path}, but not both"
+            )),
+            _ => Ok(Capture {
+                json: raw.json,
+                header: raw.header,
+                regex: raw.regex,
+            }),
+        }
+    }
+}
+
+impl Capture {
+    /// Whether a value captured as `name` is a secret, because the name looks like one or it is
+    /// taken from a sensitive header, like `Set-Cookie`
+    pub fn is_secret(&self, name: &str) -> bool {
+        redact::is_secret_name(name)
+            || self.header.as_deref().is_some_and(redact::is_sensitive_header)
+    }
+
+    pub fn extract(&self, headers: &HeaderMap, body: &str) -> Result<Option<String>, CaptureError> {
+        let value: Option<String> = match (&self.header, &self.json) {
+            (Some(header), None) => {
+                headers.get(header).and_then(|v| v.to_str().ok()).map(String::from)
+            }
+            (None, Some(path)) => match serde_json::from_str::<serde_json::Value>(body) {
+                Ok(json) => jsonpath::select_str(&json, path).map_err(CaptureError::JsonPath)?,
+                Err(e) => {
+                    log::warn!("Unable to parse body as JSON: {e}");
+                    None
+                }
+            },
+            (None, None) => Some(body.to_string()),
+            (Some(_), Some(_)) => unreachable!("Rejected when parsing"),
+        };
+
+        match (value, &self.regex) {
+            (Some(value), Some(regex)) => {
+                let regex: Regex =
+                    Regex::new(regex).map_err(|e| CaptureError::Regex(e.to_string()))?;
+                let captured: Option<String> = regex
+                    .captures(&value)
+                    .map(|caps| caps.get(1).or_else(|| caps.get(0)).unwrap().as_str().to_string());
+                Ok(captured)
+            }
+            (value, _) => Ok(value),
+        }
+    }
+}
+
+/// Values captured from earlier responses, persisted in the directory `.fire` at the root of the
+/// project so they can be used by later requests.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct State {
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(flatten)]
+    values: BTreeMap<String, String>,
+}
+
+impl State {
+    pub fn load(project_root: &Path) -> Result<State, CaptureError> {
+        let path: PathBuf = project_root.join(STATE_DIR).join(STATE_FILE);
+        let state: State = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content)
+                .map_err(|e| CaptureError::State(path.clone(), e.to_string()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => State::default(),
+            Err(e) => return Err(CaptureError::State(path, e.to_string())),
+        };
+        Ok(State { path, ..state })
+    }
+
+    pub fn insert(&mut self, key: String, value: String) {
+        self.values.insert(key, value);
+    }
+
+    pub fn save(&self) -> Result<(), CaptureError> {
+        let err = |e: std::io::Error| CaptureError::State(self.path.clone(), e.to_string());
+        let dir: &Path = self.path.parent().unwrap();
+        if !dir.exists() {
+            std::fs::create_dir_all(dir).map_err(err)?;
+            // Captured values are often secrets, such as tokens, and should never be committed
+            std::fs::write(dir.join(".gitignore"), "*\n").map_err(err)?;
+        }
+        let content: String = serde_json::to_string_pretty(&self).unwrap();
+        std::fs::write(&self.path, content).map_err(err)
+    }
+
+    pub fn properties(&self) -> Vec<Property> {
+        self.values
+            .iter()
+            .filter_map(|(k, v)| {
+                Property::new(format!("{PREFIX}{k}"), v.clone(), Source::Captured)
+                    .map(|prop| prop.with_secret(redact::is_secret_name(k)).with_origin(&self.path))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use super::Capture;
+
+    #[test]
+    fn test_extract_captures() {
+        let captures: BTreeMap<String, Capture> = serde_yaml::from_str(
+            r###"
+            token:
+              json: $.data.token
+            request_id:
+              header: x-request-id
+            order:
+              header: location
+              regex: /orders/(\d+)
+            greeting:
+              regex: hello \w+
+            "###,
+        )
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("abc-123"));
+        headers.insert("location", HeaderValue::from_static("/orders/42"));
+        let body = r#"{"data": {"token": "secret"}, "msg": "hello world"}"#;
+
+        let extract = |name: &str| captures[name].extract(&headers, body).unwrap();
+
+        assert_eq!(Some("secret".to_string()), extract("token"));
+        assert_eq!(Some("abc-123".to_string()), extract("request_id"));
+        assert_eq!(Some("42".to_string()), extract("order"));
+        assert_eq!(Some("hello world".to_string()), extract("greeting"));
+        assert!(captures["token"].is_secret("token"));
+        assert!(!captures["order"].is_secret("order"));
+    }
+
+    #[test]
+    fn test_reject_header_and_json() {
+        let capture = serde_yaml::from_str::<Capture>("header: x-token\njson: $.token");
+        assert!(capture.unwrap_err().to_string().contains("not both"));
+    }
+}
@@ -0,0 +1,586 @@
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::http::{HttpRequest, Verb};
+use crate::redact;
+
+const CONTENT_TYPE_KEY: &str = "content-type";
+const CONTENT_TYPE_FORM: &str = "application/x-www-form-urlencoded";
+
+/// A request parsed from the command line of a curl command, such as those copied from the
+/// developer tools of a browser.
+#[derive(Debug, Default)]
+pub struct CurlCommand {
+    method: Option<String>,
+    url: Option<String>,
+    headers: Vec<(String, String)>,
+    data: Vec<Data>,
+    form: Vec<(String, String)>,
+    upload: Option<String>,
+    get: bool,
+}
+
+/// Data given with any of the `--data` options
+#[derive(Debug)]
+enum Data {
+    /// Data that is sent as it is
+    Raw(String),
+    /// Data that should be URL encoded, in the format of `--data-urlencode`
+    Encode(String),
+    /// Data that is read from a file
+    File(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Opt {
+    Request,
+    Header,
+    Data,
+    DataRaw,
+    DataUrlencode,
+    User,
+    Form,
+    FormString,
+    Get,
+    Head,
+    Url,
+    UserAgent,
+    Referer,
+    Cookie,
+    UploadFile,
+    Compressed,
+    /// An option that has no equivalent in a request file, and whether it takes a value
+    Ignored(bool),
+}
+
+#[derive(Debug)]
+pub enum CurlError {
+    Syntax(String),
+    MissingValue(String),
+    MissingUrl,
+    Unsupported(String),
+}
+
+impl CurlCommand {
+    /// Parse a curl command, given as a single string as it would be written in a shell
+    pub fn parse(command: &str) -> Result<CurlCommand, CurlError> {
+        CurlCommand::from_args(split(command)?)
+    }
+
+    /// Parse a curl command that has already been split into its arguments
+    pub fn from_args(args: Vec<String>) -> Result<CurlCommand, CurlError> {
+        let mut args = args.into_iter().peekable();
+        if args.peek().map(String::as_str) == Some("curl") {
+            args.next();
+        }
+
+        let mut cmd = CurlCommand::default();
+        while let Some(arg) = args.next() {
+            let options: Vec<(Opt, String, Option<String>)> = match arg.strip_prefix("--") {
+                Some(name) => vec![(long(name), arg.clone(), None)],
+                None if arg.starts_with('-') && arg.len() > 1 => short(&arg[1..]),
+                None => {
+                    match cmd.url {
+                        None => cmd.url = Some(arg),
+                        Some(_) => log::warn!("Only one URL is supported, ignoring {arg}"),
+                    }
+                    continue;
+                }
+            };
+
+            for (opt, name, attached) in options {
+                let value: Option<String> = match (opt.takes_value(), attached) {
+                    (false, _) => None,
+                    (true, Some(value)) => Some(value),
+                    (true, None) => {
+                        Some(args.next().ok_or_else(|| CurlError::MissingValue(name.clone()))?)
+                    }
+                };
+                cmd.apply(opt, &name, value.unwrap_or_default());
+            }
+        }
+
+        Ok(cmd)
+    }
+
+    fn apply(&mut self, opt: Opt, name: &str, value: String) {
+        match opt {
+            Opt::Request => self.method = Some(value.to_uppercase()),
+            Opt::Header => match value.split_once(':') {
+                Some((key, value)) => self.headers.push((key.trim().into(), value.trim().into())),
+                None => log::warn!("Ignoring header without value {value}"),
+            },
+            Opt::Data => match value.strip_prefix('@') {
+                Some(file) => self.data.push(Data::File(file.to_string())),
+                None => self.data.push(Data::Raw(value)),
+            },
+            Opt::DataRaw => self.data.push(Data::Raw(value)),
+            Opt::DataUrlencode => self.data.push(Data::Encode(value)),
+            Opt::User => {
+                let credentials: String = base64::encode(value);
+                self.headers
+                    .push((String::from("authorization"), format!("Basic {credentials}")));
+            }
+            Opt::Form | Opt::FormString => match value.split_once('=') {
+                Some((key, value)) => self.form.push((key.to_string(), value.to_string())),
+                None => log::warn!("Ignoring form field without value {value}"),
+            },
+            Opt::Get => self.get = true,
+            Opt::Head => self.method = Some(String::from("HEAD")),
+            Opt::Url => self.url = Some(value),
+            Opt::UserAgent => self.headers.push((String::from("user-agent"), value)),
+            Opt::Referer => self.headers.push((String::from("referer"), value)),
+            Opt::Cookie if value.contains('=') => {
+                self.headers.push((String::from("cookie"), value))
+            }
+            Opt::Cookie => log::warn!("Ignoring cookies read from file {value}"),
+            Opt::UploadFile => self.upload = Some(value),
+            Opt::Compressed => log::info!("Option {name} is ignored, compression is not supported"),
+            Opt::Ignored(_) => log::info!("Option {name} has no equivalent and is ignored"),
+        }
+    }
+
+    fn method(&self) -> String {
+        match (&self.method, self.get) {
+            (Some(method), _) => method.clone(),
+            (None, true) => String::from("GET"),
+            (None, false) if self.upload.is_some() => String::from("PUT"),
+            (None, false) if !self.data.is_empty() || !self.form.is_empty() => String::from("POST"),
+            (None, false) => String::from("GET"),
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// All data joined as it would be sent by curl, unless any of the data is read from a file
+    fn joined_data(&self) -> Option<String> {
+        let parts: Option<Vec<String>> = self
+            .data
+            .iter()
+            .map(|data| match data {
+                Data::Raw(data) => Some(data.clone()),
+                Data::Encode(data) => Some(urlencode(data)),
+                Data::File(_) => None,
+            })
+            .collect();
+        parts.map(|parts| parts.join("&"))
+    }
+
+    /// Convert the command into a request, in the format of a request file
+    pub fn to_request(&self) -> Result<Mapping, CurlError> {
+        let url: &str = self.url.as_deref().ok_or(CurlError::MissingUrl)?;
+        let mut request = Mapping::new();
+        request.insert("method".into(), self.method().into());
+        request.insert("url".into(), url.into());
+
+        if self.get && !self.data.is_empty() {
+            let data: String = self.joined_data().ok_or_else(|| {
+                CurlError::Unsupported(String::from("Data read from a file with --get"))
+            })?;
+            request.insert("query".into(), Value::Mapping(pairs(&data)));
+        }
+
+        if !self.headers.is_empty() {
+            let headers: Mapping = self
+                .headers
+                .iter()
+                .map(|(k, v)| (k.as_str().into(), v.as_str().into()))
+                .collect();
+            request.insert("headers".into(), Value::Mapping(headers));
+        }
+
+        if !self.form.is_empty() {
+            let parts: Mapping =
+                self.form.iter().map(|(k, v)| (k.as_str().into(), part(v))).collect();
+            request.insert("multipart".into(), Value::Mapping(parts));
+        } else if let Some(file) = &self.upload {
+            request.insert("body_file".into(), file.as_str().into());
+        } else if !self.get && !self.data.is_empty() {
+            let (key, body): (&str, Value) = self.body()?;
+            request.insert(key.into(), body);
+        }
+
+        Ok(request)
+    }
+
+    fn body(&self) -> Result<(&'static str, Value), CurlError> {
+        if let [Data::File(file)] = self.data.as_slice() {
+            return Ok(("body_file", file.as_str().into()));
+        }
+
+        let data: String = self.joined_data().ok_or_else(|| {
+            CurlError::Unsupported(String::from("Combining data read from a file with other data"))
+        })?;
+
+        let content_type: &str = self.header(CONTENT_TYPE_KEY).unwrap_or(CONTENT_TYPE_FORM);
+        if content_type.contains("json") {
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&data) {
+                return Ok(("json", serde_yaml::to_value(json).unwrap()));
+            }
+        } else if content_type.starts_with(CONTENT_TYPE_FORM) {
+            return Ok(("form", Value::Mapping(pairs(&data))));
+        }
+
+        Ok(("body", data.into()))
+    }
+
+    /// The request as YAML, ready to be written to a request file
+    pub fn to_yaml(&self) -> Result<String, CurlError> {
+        Ok(serde_yaml::to_string(&self.to_request()?).unwrap())
+    }
+}
+
+impl Opt {
+    fn takes_value(&self) -> bool {
+        match self {
+            Opt::Get | Opt::Head | Opt::Compressed => false,
+            Opt::Ignored(takes_value) => *takes_value,
+            _ => true,
+        }
+    }
+}
+
+fn long(name: &str) -> Opt {
+    match name {
+        "request" => Opt::Request,
+        "header" => Opt::Header,
+        "data" | "data-ascii" | "data-binary" => Opt::Data,
+        "data-raw" => Opt::DataRaw,
+        "data-urlencode" => Opt::DataUrlencode,
+        "user" => Opt::User,
+        "form" => Opt::Form,
+        "form-string" => Opt::FormString,
+        "get" => Opt::Get,
+        "head" => Opt::Head,
+        "url" => Opt::Url,
+        "user-agent" => Opt::UserAgent,
+        "referer" => Opt::Referer,
+        "cookie" => Opt::Cookie,
+        "upload-file" => Opt::UploadFile,
+        "compressed" => Opt::Compressed,
+        "output" | "max-time" | "connect-timeout" | "write-out" | "retry" | "proxy" | "cacert"
+        | "cert" | "key" | "cookie-jar" | "max-redirs" | "resolve" | "config" => Opt::Ignored(true),
+        _ => Opt::Ignored(false),
+    }
+}
+
+/// Parse a group of short options, such as `-sSL` or `-XPOST`, where the value of the last option
+/// may be attached to it
+fn short(group: &str) -> Vec<(Opt, String, Option<String>)> {
+    let mut options: Vec<(Opt, String, Option<String>)> = Vec::new();
+    for (i, c) in group.char_indices() {
+        let opt: Opt = match c {
+            'X' => Opt::Request,
+            'H' => Opt::Header,
+            'd' => Opt::Data,
+            'u' => Opt::User,
+            'F' => Opt::Form,
+            'G' => Opt::Get,
+            'I' => Opt::Head,
+            'A' => Opt::UserAgent,
+            'e' => Opt::Referer,
+            'b' => Opt::Cookie,
+            'T' => Opt::UploadFile,
+            'o' | 'm' | 'w' | 'x' | 'E' | 'c' | 'K' => Opt::Ignored(true),
+            _ => Opt::Ignored(false),
+        };
+        let rest: &str = &group[i + c.len_utf8()..];
+        if opt.takes_value() && !rest.is_empty() {
+            options.push((opt, format!("-{c}"), Some(rest.to_string())));
+            break;
+        }
+        options.push((opt, format!("-{c}"), None));
+    }
+    options
+}
+
+/// A part of a multipart form, in the format of `--form`
+fn part(value: &str) -> Value {
+    let mut fields = value.split(';');
+    let content: &str = fields.next().unwrap_or_default();
+    let file: Option<&str> = content.strip_prefix('@').or_else(|| content.strip_prefix('<'));
+    let file: &str = match file {
+        Some(file) => file,
+        None => return content.into(),
+    };
+
+    let mut part = Mapping::new();
+    part.insert("file".into(), file.into());
+    for field in fields {
+        match field.trim().split_once('=') {
+            Some(("filename", filename)) => part.insert("filename".into(), filename.into()),
+            Some(("type", content_type)) => part.insert("content_type".into(), content_type.into()),
+            _ => None,
+        };
+    }
+    Value::Mapping(part)
+}
+
+fn pairs(data: &str) -> Mapping {
+    url::form_urlencoded::parse(data.as_bytes())
+        .map(|(k, v)| (k.as_ref().into(), v.as_ref().into()))
+        .collect()
+}
+
+/// URL encode data in the format of `--data-urlencode`, which is either `content`, `=content` or
+/// `name=content`, where only the content is encoded
+fn urlencode(data: &str) -> String {
+    let encode = |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+    match data.split_once('=') {
+        Some(("", content)) => encode(content),
+        Some((name, content)) => format!("{name}={}", encode(content)),
+        None => encode(data),
+    }
+}
+
+/// Format a request as a curl command, with all its headers including those set by default, and
+/// every argument escaped for a POSIX shell. Files are resolved relative to `base`, which should
+/// be the directory of the request file. Any of the `secrets` found in an argument is replaced with
+/// `***`.
+pub fn to_curl(request: &HttpRequest, base: &Path, secrets: &[&str]) -> String {
+    let mut args: Vec<Vec<String>> = Vec::new();
+    let url: String = request.url().unwrap().to_string();
+    match request.verb() {
+        Verb::Head => args.push(vec![String::from("curl"), String::from("--head"), url]),
+        verb => args.push(vec![
+            String::from("curl"),
+            String::from("-X"),
+            verb.to_string(),
+            url,
+        ]),
+    }
+
+    for (name, value) in &request.headers() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+        args.push(vec![String::from("-H"), format!("{name}: {value}")]);
+    }
+
+    if let Some(multipart) = request.multipart() {
+        for (name, part) in multipart.iter() {
+            let mut field: String = match (part.value(), part.file()) {
+                (Some(value), _) => format!("{name}={value}"),
+                (None, Some(file)) => format!("{name}=@{}", base.join(file).display()),
+                (None, None) => format!("{name}="),
+            };
+            if let Some(filename) = part.filename() {
+                field.push_str(&format!(";filename={filename}"));
+            }
+            if let Some(content_type) = part.content_type() {
+                field.push_str(&format!(";type={content_type}"));
+            }
+            args.push(vec![String::from("-F"), field]);
+        }
+    } else if let Some(body) = request.body() {
+        match (String::from_utf8(body), request.body_file()) {
+            (Ok(body), _) => args.push(vec![String::from("--data-raw"), body]),
+            (Err(_), Some(file)) if file.path() == Path::new("-") => {
+                args.push(vec![String::from("--data-binary"), String::from("@-")])
+            }
+            (Err(_), file) => {
+                let path: String = match file {
+                    Some(file) => base.join(file.path()).display().to_string(),
+                    None => String::from("<binary data>"),
+                };
+                args.push(vec![String::from("--data-binary"), format!("@{path}")])
+            }
+        }
+    }
+
+    args.into_iter()
+        .map(|arg| arg.iter().map(|a| escape(&redact(a, secrets))).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<String>>()
+        .join(" \\\n  ")
+}
+
+fn redact(arg: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| !secret.is_empty())
+        .fold(arg.to_string(), |arg, secret| arg.replace(secret, redact::REDACTED))
+}
+
+/// Escape an argument for a POSIX shell, by quoting it with single quotes unless it only consists
+/// of characters that are safe without quotes
+pub fn escape(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "-_./:=@,%+".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Split a command line into its arguments like a POSIX shell would, supporting single and double
+/// quotes, ANSI-C quotes (`$'...'`), escapes and line continuations
+pub fn split(command: &str) -> Result<Vec<String>, CurlError> {
+    let mut args: Vec<String> = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = command.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => args.extend(current.take()),
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(c) => current.get_or_insert_with(String::new).push(c),
+                None => return Err(CurlError::Syntax(String::from("Trailing backslash"))),
+            },
+            '\'' => {
+                let arg: &mut String = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(CurlError::Syntax(String::from("Unclosed quote '"))),
+                    }
+                }
+            }
+            '"' => {
+                let arg: &mut String = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => arg.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => {
+                                return Err(CurlError::Syntax(String::from("Unclosed quote \"")))
+                            }
+                        },
+                        Some(c) => arg.push(c),
+                        None => return Err(CurlError::Syntax(String::from("Unclosed quote \""))),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                let arg: &mut String = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => arg.push(ansi_c_escape(&mut chars)?),
+                        Some(c) => arg.push(c),
+                        None => return Err(CurlError::Syntax(String::from("Unclosed quote $'"))),
+                    }
+                }
+            }
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    args.extend(current);
+    Ok(args)
+}
+
+fn ansi_c_escape(chars: &mut impl Iterator<Item = char>) -> Result<char, CurlError> {
+    let c: char = chars
+        .next()
+        .ok_or_else(|| CurlError::Syntax(String::from("Unclosed quote $'")))?;
+    let escaped: char = match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        'x' | 'u' => {
+            let len: usize = if c == 'x' { 2 } else { 4 };
+            let hex: String = chars.take(len).collect();
+            u32::from_str_radix(&hex, 16)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or_else(|| CurlError::Syntax(format!("Invalid escape \\{c}{hex}")))?
+        }
+        c => c,
+    };
+    Ok(escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_yaml::Value;
+
+    use std::path::Path;
+    use std::str::FromStr;
+
+    use super::{to_curl, CurlCommand};
+    use crate::http::HttpRequest;
+
+    #[test]
+    fn test_import_curl_command() {
+        let command = r#"curl 'https://example.com/api/users?page=2' \
+  -H 'accept: application/json' \
+  -H "content-type: application/json" \
+  --data-raw $'{"name":"Tom\'s","roles":["admin"]}' \
+  -u tom:secret --compressed -sSL"#;
+
+        let request = CurlCommand::parse(command).unwrap().to_request().unwrap();
+        let headers = request["headers"].as_mapping().unwrap();
+
+        assert_eq!(request["method"], "POST");
+        assert_eq!(request["url"], "https://example.com/api/users?page=2");
+        assert_eq!(headers["accept"], "application/json");
+        assert_eq!(headers["authorization"], "Basic dG9tOnNlY3JldA==");
+        assert_eq!(request["json"]["name"], "Tom's");
+        assert_eq!(request["json"]["roles"][0], "admin");
+
+        let request: HttpRequest = HttpRequest::try_from(Value::Mapping(request)).unwrap();
+        assert_eq!(r#"{"name":"Tom's","roles":["admin"]}"#.as_bytes(), request.body().unwrap());
+    }
+
+    #[test]
+    fn test_import_curl_form_and_query() {
+        let command = "curl -G https://example.com/search -d q=fire --data-urlencode 'tag=a b'";
+        let request = CurlCommand::parse(command).unwrap().to_request().unwrap();
+        assert_eq!(request["method"], "GET");
+        assert_eq!(request["query"]["tag"], "a b");
+
+        let command = "curl -F name=tom -F 'avatar=@me.png;type=image/png' https://example.com";
+        let request = CurlCommand::parse(command).unwrap().to_request().unwrap();
+        assert_eq!(request["method"], "POST");
+        assert_eq!(request["multipart"]["name"], "tom");
+        assert_eq!(request["multipart"]["avatar"]["file"], "me.png");
+        assert_eq!(request["multipart"]["avatar"]["content_type"], "image/png");
+    }
+
+    #[test]
+    fn test_export_curl_command() {
+        let request = HttpRequest::from_str(
+            r#"
+            method: POST
+            url: https://example.com/users
+            query:
+              page: 2
+            headers:
+              authorization: Bearer s3cret
+            json:
+              name: Tom's
+            "#,
+        )
+        .unwrap();
+
+        let curl: String = to_curl(&request, Path::new("."), &["s3cret"]);
+        let lines: Vec<&str> = curl.lines().collect();
+
+        assert_eq!("curl -X POST 'https://example.com/users?page=2' \\", lines[0]);
+        assert!(lines.contains(&"  -H 'authorization: Bearer ***' \\"));
+        assert!(lines.contains(&"  -H 'content-type: application/json' \\"));
+        assert_eq!("  --data-raw '{\"name\":\"Tom'\\''s\"}'", lines[lines.len() - 1]);
+
+        let imported = CurlCommand::parse(&to_curl(&request, Path::new("."), &[])).unwrap();
+        let imported = imported.to_request().unwrap();
+        assert_eq!(imported["headers"]["authorization"], "Bearer s3cret");
+        assert_eq!(imported["json"]["name"], "Tom's");
+    }
+}
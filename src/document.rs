@@ -0,0 +1,489 @@
+use std::path::Path;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::http::HttpRequest;
+use crate::httpfile::{self, HttpFile};
+use crate::prop::{Property, Source};
+use crate::template::{self, substitution, Options, SubstitutionError};
+
+const REQUESTS_KEY: &str = "requests:";
+const NAME_KEY: &str = "name:";
+const BASE_URL_KEY: &str = "base_url";
+const HEADERS_KEY: &str = "headers";
+const URL_KEY: &str = "url";
+
+/// All requests found in a request file.
+///
+/// A file can contain several YAML documents separated by `---`, where each document is either a
+/// single request, or a collection of named requests under the key `requests`. Any other keys of a
+/// collection, such as `base_url` and `headers`, are used as defaults for all its requests.
+///
+/// The file is split into its requests before any template substitution is made, so that only the
+/// selected request needs to have all of its variables resolved.
+#[derive(Debug)]
+pub struct RequestFile {
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    position: usize,
+    name: Option<String>,
+    comment: Option<String>,
+    defaults: Option<String>,
+    template: String,
+    format: Format,
+}
+
+/// The format of the template of a request
+#[derive(Debug, Clone)]
+enum Format {
+    Yaml,
+    /// A request in a `.http` file, with the variables declared in that file
+    Http(Vec<(String, String)>),
+}
+
+/// A request in the `requests` mapping of a collection, with the lines of its template still
+/// indented as in the file
+struct NamedRequest {
+    name: String,
+    comment: Option<String>,
+    lines: Vec<String>,
+}
+
+impl NamedRequest {
+    fn template(&self) -> String {
+        let indent: usize = self
+            .lines
+            .iter()
+            .filter(|line| !is_blank_or_comment(line))
+            .map(|line| indentation(line))
+            .min()
+            .unwrap_or(0);
+
+        self.lines
+            .iter()
+            .map(|line| line.get(indent..).unwrap_or_default())
+            .collect::<Vec<&str>>()
+            .join("\n")
+    }
+}
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Substitution(SubstitutionError),
+    Parse(String),
+    NotFound(String, Vec<String>),
+    Ambiguous(Vec<String>),
+    Empty,
+}
+
+impl From<SubstitutionError> for DocumentError {
+    fn from(e: SubstitutionError) -> Self {
+        DocumentError::Substitution(e)
+    }
+}
+
+impl From<serde_yaml::Error> for DocumentError {
+    fn from(e: serde_yaml::Error) -> Self {
+        DocumentError::Parse(e.to_string())
+    }
+}
+
+impl RequestFile {
+    /// Parse a request file, in the `.http` format if the file has any of its extensions or
+    /// otherwise as YAML
+    pub fn parse_file(path: &Path, input: &str) -> RequestFile {
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        if httpfile::EXTENSIONS.contains(&extension.as_ref()) {
+            RequestFile::parse_http(input)
+        } else {
+            RequestFile::parse(input)
+        }
+    }
+
+    fn parse_http(input: &str) -> RequestFile {
+        let HttpFile {
+            requests,
+            variables,
+        } = httpfile::split(input);
+
+        let entries: Vec<Entry> = requests
+            .into_iter()
+            .enumerate()
+            .map(|(i, request)| Entry {
+                position: i + 1,
+                name: request.name,
+                comment: request.comment,
+                defaults: None,
+                template: request.template,
+                format: Format::Http(variables.clone()),
+            })
+            .collect();
+
+        RequestFile { entries }
+    }
+
+    pub fn parse(input: &str) -> RequestFile {
+        let mut entries: Vec<Entry> = Vec::new();
+        for document in documents(input) {
+            if !has_content(&document) {
+                continue;
+            }
+
+            match split_collection(&document) {
+                Some((defaults, requests)) => {
+                    for request in requests {
+                        entries.push(Entry {
+                            position: entries.len() + 1,
+                            template: request.template(),
+                            name: Some(request.name),
+                            comment: request.comment,
+                            defaults: Some(defaults.clone()),
+                            format: Format::Yaml,
+                        });
+                    }
+                }
+                None => entries.push(Entry {
+                    position: entries.len() + 1,
+                    name: document_name(&document),
+                    comment: leading_comment(&document),
+                    defaults: None,
+                    template: document,
+                    format: Format::Yaml,
+                }),
+            }
+        }
+
+        RequestFile { entries }
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Select a request by its name, or by its position in the file (starting from 1). If no name
+    /// is given, the file must contain exactly one request.
+    pub fn select(self, name: Option<&str>) -> Result<Entry, DocumentError> {
+        let labels: Vec<String> = self.entries.iter().map(Entry::label).collect();
+        match name {
+            None => match self.entries.len() {
+                0 => Err(DocumentError::Empty),
+                1 => Ok(self.entries.into_iter().next().unwrap()),
+                _ => Err(DocumentError::Ambiguous(labels)),
+            },
+            Some(name) => {
+                let index: Option<usize> = self
+                    .entries
+                    .iter()
+                    .position(|entry| entry.name.as_deref() == Some(name))
+                    .or_else(|| labels.iter().position(|label| label == name));
+
+                match index {
+                    Some(index) => Ok(self.entries.into_iter().nth(index).unwrap()),
+                    None => Err(DocumentError::NotFound(name.to_string(), labels)),
+                }
+            }
+        }
+    }
+}
+
+impl Entry {
+    /// The name of the request, or its position in the file if it has no name
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.position.to_string())
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    pub fn render(
+        &self,
+        vars: Vec<Property>,
+        options: &Options,
+    ) -> Result<HttpRequest, DocumentError> {
+        if let Format::Http(variables) = &self.format {
+            return self.render_http(variables, vars, options);
+        }
+
+        let request: String = substitution(self.template.clone(), vars.clone(), options)?;
+        let request: Mapping = serde_yaml::from_str(&request)?;
+        let request: Mapping = match &self.defaults {
+            Some(defaults) => {
+                let defaults: String = substitution(defaults.clone(), vars, options)?;
+                let defaults: Mapping = serde_yaml::from_str(&defaults)?;
+                apply_defaults(defaults, request)
+            }
+            None => request,
+        };
+
+        Ok(HttpRequest::try_from(Value::Mapping(request))?)
+    }
+}
+
+impl Entry {
+    /// The variables used by the request, except for those declared in a `.http` file
+    pub fn variables(&self, options: &Options) -> Result<Vec<String>, SubstitutionError> {
+        let mut templates: Vec<&str> = vec![&self.template];
+        templates.extend(self.defaults.as_deref());
+        let declared: &[(String, String)] = match &self.format {
+            Format::Http(variables) => {
+                templates.extend(variables.iter().map(|(_, value)| value.as_str()));
+                variables
+            }
+            _ => &[],
+        };
+
+        let mut names: Vec<String> = Vec::new();
+        for template in templates {
+            for name in template::variables(template, options)? {
+                if !names.contains(&name) && !declared.iter().any(|(key, _)| *key == name) {
+                    names.push(name);
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Render a request from a `.http` file. Variables declared in the file may refer to earlier
+    /// variables, and can be overridden by variables from environment files, captured values and
+    /// variables given as arguments, but not by system environment variables.
+    fn render_http(
+        &self,
+        variables: &[(String, String)],
+        mut vars: Vec<Property>,
+        options: &Options,
+    ) -> Result<HttpRequest, DocumentError> {
+        for (key, value) in variables {
+            let value: String = substitution(value.clone(), vars.clone(), options)?;
+            vars.push(Property::new(key.clone(), value, Source::File(0, None)).unwrap());
+        }
+
+        let request: String = substitution(self.template.clone(), vars, options)?;
+        let request: Mapping = httpfile::to_request(&request).map_err(DocumentError::Parse)?;
+        Ok(HttpRequest::try_from(Value::Mapping(request))?)
+    }
+}
+
+fn apply_defaults(mut defaults: Mapping, mut request: Mapping) -> Mapping {
+    let base_url: Option<Value> = defaults.remove(BASE_URL_KEY);
+    for (key, default) in defaults {
+        let case_insensitive: bool = key.as_str() == Some(HEADERS_KEY);
+        match (request.get_mut(&key), default) {
+            (None, default) => {
+                request.insert(key, default);
+            }
+            (Some(Value::Mapping(values)), Value::Mapping(defaults)) => {
+                for (k, v) in defaults {
+                    if !contains_key(values, &k, case_insensitive) {
+                        values.insert(k, v);
+                    }
+                }
+            }
+            (Some(_), _) => {}
+        }
+    }
+
+    if let Some(Value::String(base_url)) = base_url {
+        if let Some(Value::String(url)) = request.get_mut(URL_KEY) {
+            if !url.contains("://") {
+                *url =
+                    format!("{}/{}", base_url.trim_end_matches('/'), url.trim_start_matches('/'));
+            }
+        }
+    }
+
+    request
+}
+
+fn contains_key(mapping: &Mapping, key: &Value, case_insensitive: bool) -> bool {
+    match (key.as_str(), case_insensitive) {
+        (Some(key), true) => {
+            mapping.keys().filter_map(Value::as_str).any(|k| k.eq_ignore_ascii_case(key))
+        }
+        _ => mapping.contains_key(key),
+    }
+}
+
+fn documents(input: &str) -> Vec<String> {
+    let mut documents: Vec<Vec<&str>> = vec![Vec::new()];
+    for line in input.lines() {
+        if line.trim_end() == "---" || line.starts_with("--- ") {
+            documents.push(Vec::new());
+        } else {
+            documents.last_mut().unwrap().push(line);
+        }
+    }
+    documents.into_iter().map(|lines| lines.join("\n")).collect()
+}
+
+fn has_content(document: &str) -> bool {
+    document.lines().any(|line| !is_blank_or_comment(line))
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let line: &str = line.trim();
+    line.is_empty() || line.starts_with('#')
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn uncomment(line: &str) -> String {
+    line.trim().trim_start_matches('#').trim().to_string()
+}
+
+fn unquote(value: &str) -> String {
+    let quotes: &[_] = &['\'', '"'];
+    value.trim().trim_matches(quotes).to_string()
+}
+
+fn join_comment(lines: Vec<String>) -> Option<String> {
+    let comment: String = lines
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join(" ");
+    if comment.is_empty() {
+        None
+    } else {
+        Some(comment)
+    }
+}
+
+fn document_name(document: &str) -> Option<String> {
+    document.lines().find_map(|line| line.strip_prefix(NAME_KEY)).map(unquote)
+}
+
+fn leading_comment(document: &str) -> Option<String> {
+    let comment: Vec<String> = document
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .take_while(|line| line.trim().starts_with('#'))
+        .map(uncomment)
+        .collect();
+    join_comment(comment)
+}
+
+/// Split a document with a top level `requests` key into the document without that key, which
+/// holds defaults for all requests, and the name, comment and template of each request.
+fn split_collection(document: &str) -> Option<(String, Vec<NamedRequest>)> {
+    let lines: Vec<&str> = document.lines().collect();
+    let start: usize = lines.iter().position(|line| is_requests_key(line))?;
+    let end: usize = lines[start + 1..]
+        .iter()
+        .position(|line| !line.is_empty() && !line.starts_with([' ', '#']))
+        .map(|i| start + 1 + i)
+        .unwrap_or(lines.len());
+
+    let defaults: String = lines[..start]
+        .iter()
+        .chain(lines[end..].iter())
+        .copied()
+        .collect::<Vec<&str>>()
+        .join("\n");
+
+    let block: &[&str] = &lines[start + 1..end];
+    let indent: usize = block
+        .iter()
+        .find(|line| !is_blank_or_comment(line))
+        .map(|line| indentation(line))?;
+
+    let mut requests: Vec<NamedRequest> = Vec::new();
+    let mut current: Option<NamedRequest> = None;
+    let mut comment: Vec<String> = Vec::new();
+
+    for line in block {
+        let line_indent: usize = indentation(line);
+        if line.trim().is_empty() {
+            if let Some(request) = &mut current {
+                request.lines.push(String::new());
+            }
+        } else if line_indent > indent {
+            if let Some(request) = &mut current {
+                request.lines.push(line.to_string());
+            }
+        } else if line.trim().starts_with('#') {
+            comment.push(uncomment(line));
+        } else {
+            requests.extend(current.take());
+            let (name, rest) = line.trim().split_once(':').unwrap_or((line.trim(), ""));
+            let rest: String = rest.trim().to_string();
+            let lines: Vec<String> =
+                if rest.is_empty() || rest.starts_with('#') { Vec::new() } else { vec![rest] };
+            current = Some(NamedRequest {
+                name: unquote(name),
+                comment: join_comment(std::mem::take(&mut comment)),
+                lines,
+            });
+        }
+    }
+
+    requests.extend(current);
+
+    Some((defaults, requests))
+}
+
+fn is_requests_key(line: &str) -> bool {
+    match line.strip_prefix(REQUESTS_KEY) {
+        Some(rest) => rest.trim().is_empty() || rest.trim().starts_with('#'),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::HeaderMap;
+
+    use super::{DocumentError, Entry, Options, RequestFile};
+
+    #[test]
+    fn test_select_request_from_documents_and_collection() -> Result<(), DocumentError> {
+        let input = r###"
+# Check that the service is up
+name: ping
+method: GET
+url: https://example.com/ping
+---
+base_url: https://{{HOST}}/api/
+headers:
+  accept: application/json
+  x-tenant: acme
+requests:
+  # Log in and receive a token
+  login:
+    method: POST
+    url: /login
+    headers:
+      Accept: text/plain
+  users:
+    method: GET
+    url: users
+---
+method: GET
+url: https://other.com/{{MISSING}}
+"###;
+
+        let file = RequestFile::parse(input);
+        let labels: Vec<String> = file.entries().iter().map(Entry::label).collect();
+        assert_eq!(vec!["ping", "login", "users", "4"], labels);
+        assert_eq!(Some("Check that the service is up"), file.entries()[0].comment());
+        assert_eq!(Some("Log in and receive a token"), file.entries()[1].comment());
+
+        let vars = vec!["HOST=example.com".parse().unwrap()];
+        let login = file.select(Some("login"))?.render(vars, &Options::default())?;
+        let headers: HeaderMap = login.headers();
+
+        assert_eq!("https://example.com/api/login", login.url().unwrap().as_str());
+        assert_eq!(vec!["text/plain"], headers.get_all("accept").iter().collect::<Vec<_>>());
+        assert_eq!("acme", headers.get("x-tenant").unwrap());
+
+        let file = RequestFile::parse(input);
+        assert!(matches!(file.select(None), Err(DocumentError::Ambiguous(_))));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,267 @@
+use std::fmt::Display;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// An error in an environment file, on a line starting from 1
+#[derive(Debug, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parse the content of an environment file in the dotenv format, in which
+/// - blank lines and lines starting with `#` are ignored
+/// - entries may start with `export `
+/// - values in single quotes are used as they are
+/// - values in double quotes may contain escape sequences like `\n` and span several lines
+/// - unquoted values end at a ` #` comment and are trimmed
+/// - `${KEY}` in unquoted and double quoted values is replaced by the value of an earlier key in
+///   the file, or an environment variable
+pub fn parse(content: &str) -> Result<Vec<(String, String)>, Error> {
+    let mut parser = Parser {
+        chars: content.chars().peekable(),
+        line: 1,
+        entries: Vec::new(),
+    };
+    parser.parse()?;
+    Ok(parser.entries)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    entries: Vec<(String, String)>,
+}
+
+impl Parser<'_> {
+    fn parse(&mut self) -> Result<(), Error> {
+        loop {
+            self.skip_while(char::is_whitespace);
+            match self.chars.peek() {
+                None => return Ok(()),
+                Some('#') => self.skip_while(|c| c != '\n'),
+                Some(_) => {
+                    let entry: (String, String) = self.entry()?;
+                    self.entries.push(entry);
+                }
+            }
+        }
+    }
+
+    fn entry(&mut self) -> Result<(String, String), Error> {
+        let mut key: String = self.take_while(|c| c != '=' && !c.is_whitespace());
+        if key == "export" && self.chars.peek().is_some_and(|c| *c == ' ' || *c == '\t') {
+            self.skip_while(is_blank);
+            key = self.take_while(|c| c != '=' && !c.is_whitespace());
+        }
+        self.skip_while(is_blank);
+
+        if key.is_empty() {
+            return Err(self.error("Missing key before '='"));
+        }
+        if !is_valid_key(&key) {
+            return Err(self.error(format!("Invalid key {key:?}")));
+        }
+        if self.chars.next_if_eq(&'=').is_none() {
+            return Err(self.error(format!("Expected '=' after key {key}")));
+        }
+        self.skip_while(is_blank);
+
+        let start: usize = self.line;
+        let value: String = match self.chars.peek() {
+            Some('\'') => self.single_quoted(start)?,
+            Some('"') => self.double_quoted(start)?,
+            _ => {
+                let value: String = self.take_while(|c| c != '\n');
+                let value: &str = match value.find(" #").or_else(|| value.find("\t#")) {
+                    Some(comment) => &value[..comment],
+                    None => &value,
+                };
+                self.interpolate(value.trim())?
+            }
+        };
+        Ok((key, value))
+    }
+
+    fn single_quoted(&mut self, start: usize) -> Result<String, Error> {
+        self.chars.next();
+        let value: String = self.take_while(|c| c != '\'');
+        if self.chars.next().is_none() {
+            return Err(Error {
+                line: start,
+                message: String::from("Missing closing ' of value"),
+            });
+        }
+        self.end_of_line()?;
+        Ok(value)
+    }
+
+    fn double_quoted(&mut self, start: usize) -> Result<String, Error> {
+        self.chars.next();
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                None => {
+                    return Err(Error {
+                        line: start,
+                        message: String::from("Missing closing \" of value"),
+                    })
+                }
+                Some('"') => break,
+                Some('\\') => match self.next() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('"' | '\\' | '$' | '\'')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => continue,
+                },
+                Some('$') if self.chars.peek() == Some(&'{') => {
+                    let name: String = self.variable_name()?;
+                    value.push_str(&self.lookup(&name));
+                }
+                Some(c) => value.push(c),
+            }
+        }
+        self.end_of_line()?;
+        Ok(value)
+    }
+
+    /// Replace each `${KEY}` in an unquoted value
+    fn interpolate(&self, value: &str) -> Result<String, Error> {
+        let mut output = String::with_capacity(value.len());
+        let mut rest: &str = value;
+        while let Some(start) = rest.find("${") {
+            output.push_str(&rest[..start]);
+            let end: usize = rest[start..]
+                .find('}')
+                .ok_or_else(|| self.error("Missing closing } of variable"))?;
+            output.push_str(&self.lookup(&rest[start + 2..start + end]));
+            rest = &rest[start + end + 1..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    fn variable_name(&mut self) -> Result<String, Error> {
+        self.chars.next();
+        let name: String = self.take_while(|c| c != '}' && c != '"' && c != '\n');
+        match self.chars.next_if_eq(&'}') {
+            Some(_) => Ok(name),
+            None => Err(self.error("Missing closing } of variable")),
+        }
+    }
+
+    /// The value of the last earlier entry with `name`, or otherwise the environment variable
+    fn lookup(&self, name: &str) -> String {
+        let entry: Option<&(String, String)> = self.entries.iter().rev().find(|(k, _)| k == name);
+        match entry {
+            Some((_, value)) => value.clone(),
+            None => std::env::var(name).unwrap_or_else(|_| {
+                log::warn!("Variable {name} on line {} has no value", self.line);
+                String::new()
+            }),
+        }
+    }
+
+    /// Only whitespace and a comment may follow a quoted value
+    fn end_of_line(&mut self) -> Result<(), Error> {
+        self.skip_while(is_blank);
+        match self.chars.peek().copied() {
+            None | Some('\n') | Some('\r') | Some('#') => {
+                self.skip_while(|c| c != '\n');
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("Unexpected {c:?} after quoted value"))),
+        }
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c: Option<char> = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.chars.next_if(|c| predicate(*c)) {
+            if c == '\n' {
+                self.line += 1;
+            }
+            taken.push(c);
+        }
+        taken
+    }
+
+    fn skip_while(&mut self, predicate: impl Fn(char) -> bool) {
+        self.take_while(predicate);
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, Error};
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+# Comment
+export HOST=example.com
+URL=https://${HOST}/api # trailing comment
+SINGLE='no ${HOST} or \n here'
+DOUBLE="line one\nsays \"hi\" to ${HOST}"
+MULTI="first
+second"
+EMPTY=
+  SPACED = value with spaces
+"#;
+        let entries: Vec<(String, String)> = parse(content).unwrap();
+        let value = |key: &str| entries.iter().find(|(k, _)| k == key).unwrap().1.as_str();
+
+        assert_eq!("example.com", value("HOST"));
+        assert_eq!("https://example.com/api", value("URL"));
+        assert_eq!("no ${HOST} or \\n here", value("SINGLE"));
+        assert_eq!("line one\nsays \"hi\" to example.com", value("DOUBLE"));
+        assert_eq!("first\nsecond", value("MULTI"));
+        assert_eq!("", value("EMPTY"));
+        assert_eq!("value with spaces", value("SPACED"));
+
+        let unterminated = parse("A=1\n\nB=\"open\nC=3\n");
+        assert_eq!(
+            Err(Error {
+                line: 3,
+                message: String::from("Missing closing \" of value")
+            }),
+            unterminated
+        );
+        assert_eq!(2, parse("A=1\nnot an entry\n").unwrap_err().line);
+    }
+}
@@ -1,13 +1,10 @@
-use std::error::Error as StdError;
 use std::fmt::Debug;
 use std::fmt::Display;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{self, ExitCode, Termination};
 
 use reqwest::Url;
 
-pub trait Error: StdError + Termination {}
-
 pub enum FireError {
     Timeout(Url),
     Connection(Url),
@@ -16,9 +13,29 @@ pub enum FireError {
     NotAFile(PathBuf),
     GenericIO(String),
     Template(String),
+    InvalidRequest(String),
+    RequestNotFound(String),
+    Expectation(usize, usize),
+    Contract(usize, usize),
+    Failures(usize, usize),
+    Import(String),
+    External(String),
+    Environment(String),
     Other(String),
 }
 
+impl FireError {
+    /// Map an IO error that occurred while reading `path` to the matching error
+    pub fn io(err: std::io::Error, path: &Path) -> FireError {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => FireError::FileNotFound(path.to_path_buf()),
+            std::io::ErrorKind::PermissionDenied => FireError::NoReadPermission(path.to_path_buf()),
+            _ if path.is_dir() => FireError::NotAFile(path.to_path_buf()),
+            _ => FireError::GenericIO(err.to_string()),
+        }
+    }
+}
+
 impl Debug for FireError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         Display::fmt(&self, f)
@@ -35,6 +52,18 @@ impl Display for FireError {
             FireError::NotAFile(path) => format!("{:?} exists but it is not a file", path.clone()),
             FireError::NoReadPermission(path) => format!("No permission to read file {:?}", path.clone()),
             FireError::Template(msg) => format!("Unable to render request from template. {msg}"),
+            FireError::InvalidRequest(msg) => format!("Invalid request file. {msg}"),
+            FireError::RequestNotFound(msg) => msg.clone(),
+            FireError::Expectation(failed, total) => {
+                format!("{failed} of {total} expectations failed")
+            }
+            FireError::Contract(failed, total) => {
+                format!("{failed} of {total} expectations failed, the response does not conform to its schema")
+            }
+            FireError::Failures(failed, total) => format!("{failed} of {total} requests failed"),
+            FireError::Import(msg) => format!("Unable to import request. {msg}"),
+            FireError::External(msg) => format!("Template helper failed. {msg}"),
+            FireError::Environment(msg) => format!("Unable to read environment. {msg}"),
             FireError::Other(err) => format!("Error: {err}"),
         };
 
@@ -52,12 +81,20 @@ impl Termination for FireError {
             FireError::NotAFile(_) => ExitCode::from(7),
             FireError::GenericIO(_) => ExitCode::from(8),
             FireError::Template(_) => ExitCode::from(9),
+            FireError::InvalidRequest(_) => ExitCode::from(10),
+            FireError::RequestNotFound(_) => ExitCode::from(11),
+            FireError::Expectation(_, _) => ExitCode::from(12),
+            FireError::Failures(_, _) => ExitCode::from(13),
+            FireError::Import(_) => ExitCode::from(14),
+            FireError::Contract(_, _) => ExitCode::from(15),
+            FireError::External(_) => ExitCode::from(16),
+            FireError::Environment(_) => ExitCode::from(17),
             FireError::Other(_) => ExitCode::from(1),
         }
     }
 }
 
 pub fn exit(err: FireError) -> ExitCode {
-    eprintln!("{err}");
+    eprintln!("{}", crate::redact::redact(&err.to_string()));
     err.report()
 }
@@ -0,0 +1,438 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use regex::Regex;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_yaml::Value;
+
+use crate::jsonpath;
+use crate::schema::Validator;
+
+/// Expectations on a response, which makes it possible to use request files as tests.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Expectations {
+    status: Option<Status>,
+    #[serde(default)]
+    headers: BTreeMap<String, HeaderExpectation>,
+    #[serde(default)]
+    json: BTreeMap<String, serde_json::Value>,
+    #[serde(default)]
+    body_contains: OneOrMany,
+    max_latency: Option<Latency>,
+    schema: Option<Schema>,
+}
+
+/// Accepted status codes, such as `200`, `2xx`, `200-204` or a list of any of those
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Value")]
+pub struct Status(Vec<(u16, u16)>);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum HeaderExpectation {
+    Equals(String),
+    Regex { regex: String },
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    #[default]
+    None,
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Max latency, either in milliseconds or as a string with a unit, like `500ms` or `2s`
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(try_from = "Value")]
+pub struct Latency(Duration);
+
+/// A JSON Schema (draft 2020-12) for the body, either inline or as a path to a file in JSON or YAML,
+/// relative to the request file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Schema {
+    File(PathBuf),
+    Inline(serde_json::Value),
+}
+
+/// The result of one expectation
+#[derive(Debug)]
+pub struct Outcome {
+    description: String,
+    failure: Option<String>,
+    contract: bool,
+}
+
+impl Expectations {
+    pub fn evaluate(
+        &self,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+        duration: Duration,
+        base_dir: &Path,
+    ) -> Vec<Outcome> {
+        let mut outcomes: Vec<Outcome> = Vec::new();
+
+        if let Some(expected) = &self.status {
+            let failure: Option<String> =
+                (!expected.accepts(status.as_u16())).then(|| format!("was {}", status.as_u16()));
+            outcomes.push(Outcome::new(format!("status is {expected}"), failure));
+        }
+
+        for (name, expected) in &self.headers {
+            let actual: Option<&str> = headers.get(name).and_then(|v| v.to_str().ok());
+            let (description, passed): (String, Result<bool, String>) = match expected {
+                HeaderExpectation::Equals(value) => {
+                    (format!("header {name} equals {value}"), Ok(actual == Some(value)))
+                }
+                HeaderExpectation::Regex { regex } => (
+                    format!("header {name} matches {regex}"),
+                    Regex::new(regex)
+                        .map(|regex| actual.map(|v| regex.is_match(v)).unwrap_or(false))
+                        .map_err(|e| format!("invalid regex: {e}")),
+                ),
+            };
+            let failure: Option<String> = match passed {
+                Ok(true) => None,
+                Ok(false) => Some(match actual {
+                    Some(actual) => format!("was {actual}"),
+                    None => String::from("header is missing"),
+                }),
+                Err(e) => Some(e),
+            };
+            outcomes.push(Outcome::new(description, failure));
+        }
+
+        if !self.json.is_empty() {
+            let json: Result<serde_json::Value, String> =
+                serde_json::from_str(body).map_err(|e| format!("body is not JSON: {e}"));
+            for (path, expected) in &self.json {
+                let actual: Result<Option<&serde_json::Value>, String> = json
+                    .as_ref()
+                    .map_err(Clone::clone)
+                    .and_then(|json| jsonpath::select(json, path))
+                    .map(|values| values.first().copied());
+                let failure: Option<String> = match actual {
+                    Ok(Some(actual)) if actual == expected => None,
+                    Ok(Some(actual)) => Some(format!("was {actual}")),
+                    Ok(None) => Some(String::from("no value found")),
+                    Err(e) => Some(e),
+                };
+                outcomes.push(Outcome::new(format!("{path} equals {expected}"), failure));
+            }
+        }
+
+        for expected in self.body_contains.values() {
+            let failure: Option<String> =
+                (!body.contains(expected.as_str())).then(|| String::from("not found in body"));
+            outcomes.push(Outcome::new(format!("body contains {expected:?}"), failure));
+        }
+
+        if let Some(Latency(max)) = self.max_latency {
+            let failure: Option<String> =
+                (duration > max).then(|| format!("was {} ms", duration.as_millis()));
+            outcomes
+                .push(Outcome::new(format!("latency is at most {} ms", max.as_millis()), failure));
+        }
+
+        if let Some(schema) = &self.schema {
+            outcomes.extend(schema.evaluate(body, base_dir));
+        }
+
+        outcomes
+    }
+}
+
+impl Schema {
+    /// Validate the body, with an outcome for each violation of the schema
+    fn evaluate(&self, body: &str, base_dir: &Path) -> Vec<Outcome> {
+        let (description, schema): (String, Result<serde_json::Value, String>) = match self {
+            Schema::Inline(schema) => (String::from("body conforms to schema"), Ok(schema.clone())),
+            Schema::File(path) => (
+                format!("body conforms to schema {}", path.display()),
+                std::fs::read_to_string(base_dir.join(path))
+                    .map_err(|e| format!("unable to read schema: {e}"))
+                    .and_then(|s| {
+                        serde_yaml::from_str(&s).map_err(|e| format!("invalid schema: {e}"))
+                    }),
+            ),
+        };
+        let json: Result<serde_json::Value, String> =
+            serde_json::from_str(body).map_err(|e| format!("body is not JSON: {e}"));
+
+        match (schema, json) {
+            (Ok(schema), Ok(json)) => {
+                let violations = Validator::new(&schema).validate(&schema, &json);
+                if violations.is_empty() {
+                    return vec![Outcome::contract(description, None)];
+                }
+                violations
+                    .into_iter()
+                    .map(|v| Outcome::contract(description.clone(), Some(v.to_string())))
+                    .collect()
+            }
+            (Err(e), _) | (_, Err(e)) => vec![Outcome::contract(description, Some(e))],
+        }
+    }
+}
+
+impl Status {
+    fn accepts(&self, status: u16) -> bool {
+        self.0.iter().any(|(low, high)| (*low..=*high).contains(&status))
+    }
+
+    fn range(value: &str) -> Result<(u16, u16), String> {
+        let value: &str = value.trim();
+        let invalid = || format!("Invalid status {value}");
+        if let Some(class) = value.strip_suffix("xx").or_else(|| value.strip_suffix("XX")) {
+            let class: u16 = class.parse().map_err(|_| invalid())?;
+            if !(1..=5).contains(&class) {
+                return Err(format!("Invalid status class {value}, expected 1xx to 5xx"));
+            }
+            let low: u16 = class.checked_mul(100).ok_or_else(invalid)?;
+            return Ok((low, low + 99));
+        }
+        match value.split_once('-') {
+            Some((low, high)) => {
+                let low: u16 = low.trim().parse().map_err(|_| invalid())?;
+                let high: u16 = high.trim().parse().map_err(|_| invalid())?;
+                match low <= high {
+                    true => Ok((low, high)),
+                    false => {
+                        Err(format!("Invalid status range {value}, {low} is greater than {high}"))
+                    }
+                }
+            }
+            None => value.parse().map(|status| (status, status)).map_err(|_| invalid()),
+        }
+    }
+}
+
+impl TryFrom<Value> for Status {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let values: Vec<Value> = match value {
+            Value::Sequence(values) => values,
+            value => vec![value],
+        };
+        values
+            .into_iter()
+            .map(|value| match value {
+                Value::Number(n) => Status::range(&n.to_string()),
+                Value::String(s) => Status::range(&s),
+                other => Err(format!("Invalid status {:?}", other)),
+            })
+            .collect::<Result<Vec<(u16, u16)>, String>>()
+            .map(Status)
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ranges: Vec<String> = self
+            .0
+            .iter()
+            .map(|(low, high)| match (low, high) {
+                (low, high) if low == high => low.to_string(),
+                (low, high) if low % 100 == 0 && *high == low + 99 => format!("{}xx", low / 100),
+                (low, high) => format!("{low}-{high}"),
+            })
+            .collect();
+        f.write_str(&ranges.join(" or "))
+    }
+}
+
+impl OneOrMany {
+    fn values(&self) -> Vec<String> {
+        match self {
+            OneOrMany::None => Vec::new(),
+            OneOrMany::One(value) => vec![value.clone()],
+            OneOrMany::Many(values) => values.clone(),
+        }
+    }
+}
+
+impl TryFrom<Value> for Latency {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let invalid = || format!("Invalid latency {:?}, expected for example 500ms or 2s", value);
+        let millis: f64 = match &value {
+            Value::Number(n) => n.as_f64().ok_or_else(invalid)?,
+            Value::String(s) => match (s.strip_suffix("ms"), s.strip_suffix('s')) {
+                (Some(ms), _) => ms.trim().parse().map_err(|_| invalid())?,
+                (None, Some(s)) => s.trim().parse::<f64>().map_err(|_| invalid())? * 1000.0,
+                (None, None) => s.trim().parse().map_err(|_| invalid())?,
+            },
+            _ => return Err(invalid()),
+        };
+        Duration::try_from_secs_f64(millis / 1000.0).map(Latency).map_err(|_| invalid())
+    }
+}
+
+impl Outcome {
+    pub fn new(description: String, failure: Option<String>) -> Outcome {
+        Outcome {
+            description,
+            failure,
+            contract: false,
+        }
+    }
+
+    /// The outcome of validating a response against a schema or an API contract
+    pub fn contract(description: String, failure: Option<String>) -> Outcome {
+        Outcome {
+            description,
+            failure,
+            contract: true,
+        }
+    }
+
+    pub fn is_contract(&self) -> bool {
+        self.contract
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Duration;
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::StatusCode;
+    use serde_yaml::Value;
+
+    use super::{Expectations, Latency, Outcome, Status};
+
+    #[test]
+    fn test_evaluate_expectations() {
+        let expectations: Expectations = serde_yaml::from_str(
+            r###"
+            status: [2xx, 304]
+            headers:
+              content-type: application/json
+              x-request-id:
+                regex: ^[0-9]+$
+            json:
+              $.data.id: 42
+              $.data.name: tom
+            body_contains: jerry
+            max_latency: 1s
+            "###,
+        )
+        .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        headers.insert("x-request-id", HeaderValue::from_static("abc"));
+        let body = r#"{"data": {"id": 42, "name": "jerry"}}"#;
+
+        let outcomes: Vec<Outcome> = expectations.evaluate(
+            StatusCode::CREATED,
+            &headers,
+            body,
+            Duration::from_millis(1500),
+            Path::new(""),
+        );
+        let failed: Vec<&str> =
+            outcomes.iter().filter(|o| !o.passed()).map(Outcome::description).collect();
+
+        assert_eq!(7, outcomes.len());
+        assert_eq!("status is 2xx or 304", outcomes[0].description());
+        assert_eq!(
+            vec![
+                "header x-request-id matches ^[0-9]+$",
+                "$.data.name equals \"tom\"",
+                "latency is at most 1000 ms"
+            ],
+            failed
+        );
+    }
+
+    #[test]
+    fn test_evaluate_schema() {
+        let expectations: Expectations = serde_yaml::from_str(
+            r###"
+            schema:
+              type: object
+              required: [id]
+              properties:
+                id:
+                  type: integer
+                tags:
+                  type: array
+                  items:
+                    type: string
+            "###,
+        )
+        .unwrap();
+
+        let body = r#"{"tags": ["a", 2]}"#;
+        let outcomes: Vec<Outcome> = expectations.evaluate(
+            StatusCode::OK,
+            &HeaderMap::new(),
+            body,
+            Duration::ZERO,
+            Path::new(""),
+        );
+        let failures: Vec<&str> = outcomes.iter().filter_map(Outcome::failure).collect();
+
+        assert!(outcomes.iter().all(Outcome::is_contract));
+        assert_eq!(
+            vec![
+                "(root): property \"id\" is missing (required)",
+                "/tags/1: expected string, found integer (type)"
+            ],
+            failures
+        );
+
+        let file: Expectations = serde_yaml::from_str("schema: missing.json").unwrap();
+        let outcomes =
+            file.evaluate(StatusCode::OK, &HeaderMap::new(), "{}", Duration::ZERO, Path::new(""));
+        assert_eq!("body conforms to schema missing.json", outcomes[0].description());
+        assert!(!outcomes[0].passed());
+    }
+
+    #[test]
+    fn test_parse_status_classes() {
+        assert_eq!(Ok((200, 299)), Status::range("2xx"));
+        assert_eq!(Ok((500, 599)), Status::range("5XX"));
+        assert!(Status::range("0xx").is_err());
+        assert!(Status::range("6xx").is_err());
+        assert!(Status::range("700xx").is_err());
+        assert_eq!(Ok((200, 204)), Status::range("200-204"));
+        assert!(Status::range("204-200").is_err());
+    }
+
+    #[test]
+    fn test_parse_latency() {
+        let latency = |yaml: &str| Latency::try_from(serde_yaml::from_str::<Value>(yaml).unwrap());
+
+        assert_eq!(Duration::from_millis(500), latency("500ms").unwrap().0);
+        assert_eq!(Duration::from_millis(2000), latency("2s").unwrap().0);
+        assert_eq!(Duration::from_millis(250), latency("250").unwrap().0);
+        for invalid in ["-5", "-5ms", ".nan", ".inf", "1e300s", "fast"] {
+            assert!(latency(invalid).unwrap_err().starts_with("Invalid latency"), "{invalid}");
+        }
+    }
+}
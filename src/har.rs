@@ -0,0 +1,395 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Version};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+
+use crate::import::{self, Import, ImportError};
+use crate::redact;
+
+const HAR_VERSION: &str = "1.2";
+/// Headers that are left out when importing, since they are either set by fire or would make the
+/// response unreadable, in the case of `accept-encoding`
+const SKIPPED_HEADERS: [&str; 3] = ["content-length", "host", "accept-encoding"];
+
+/// An HTTP Archive (HAR), version 1.2
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Har {
+    log: Log,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Log {
+    #[serde(default)]
+    version: String,
+    #[serde(default)]
+    creator: Creator,
+    entries: Vec<Entry>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Creator {
+    name: String,
+    version: String,
+}
+
+/// A request and its response
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Entry {
+    #[serde(default)]
+    started_date_time: String,
+    #[serde(default)]
+    time: f64,
+    request: Request,
+    response: Option<Response>,
+    #[serde(default)]
+    cache: serde_json::Value,
+    #[serde(default)]
+    timings: Timings,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Request {
+    method: String,
+    url: String,
+    #[serde(default)]
+    http_version: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    #[serde(default)]
+    query_string: Vec<NameValue>,
+    #[serde(default)]
+    cookies: Vec<NameValue>,
+    #[serde(default = "unknown")]
+    headers_size: i64,
+    #[serde(default = "unknown")]
+    body_size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_data: Option<PostData>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Response {
+    status: u16,
+    #[serde(default)]
+    status_text: String,
+    #[serde(default)]
+    http_version: String,
+    #[serde(default)]
+    headers: Vec<NameValue>,
+    #[serde(default)]
+    cookies: Vec<NameValue>,
+    content: Content,
+    #[serde(default, rename = "redirectURL")]
+    redirect_url: String,
+    #[serde(default = "unknown")]
+    headers_size: i64,
+    #[serde(default = "unknown")]
+    body_size: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PostData {
+    #[serde(default)]
+    mime_type: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    params: Vec<Param>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Param {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Content {
+    #[serde(default)]
+    size: i64,
+    #[serde(default)]
+    mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NameValue {
+    name: String,
+    value: String,
+}
+
+/// Time spent in each phase of a request, in milliseconds. Since only the total time is measured,
+/// all of it is attributed to waiting for the response.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Timings {
+    #[serde(default)]
+    send: f64,
+    #[serde(default)]
+    wait: f64,
+    #[serde(default)]
+    receive: f64,
+}
+
+fn unknown() -> i64 {
+    -1
+}
+
+impl Har {
+    pub fn new(entries: Vec<Entry>) -> Har {
+        Har {
+            log: Log {
+                version: String::from(HAR_VERSION),
+                creator: Creator {
+                    name: String::from("fire"),
+                    version: String::from(clap::crate_version!()),
+                },
+                entries,
+            },
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl Entry {
+    pub fn new(
+        started: SystemTime,
+        duration: Duration,
+        request: Request,
+        response: Response,
+    ) -> Entry {
+        let time: f64 = duration.as_secs_f64() * 1000.0;
+        Entry {
+            started_date_time: humantime::format_rfc3339_millis(started).to_string(),
+            time,
+            request,
+            response: Some(response),
+            cache: serde_json::Value::Object(Default::default()),
+            timings: Timings {
+                send: 0.0,
+                wait: time,
+                receive: 0.0,
+            },
+        }
+    }
+}
+
+impl From<&reqwest::blocking::Request> for Request {
+    fn from(req: &reqwest::blocking::Request) -> Self {
+        let body: Option<&[u8]> = req.body().and_then(|body| body.as_bytes());
+        let mime_type: String = header(req.headers(), "content-type").unwrap_or_default();
+        let post_data: Option<PostData> = match body {
+            Some(body) => Some(PostData {
+                mime_type,
+                params: Vec::new(),
+                text: Some(String::from_utf8_lossy(body).to_string()),
+            }),
+            None if req.body().is_some() => Some(PostData {
+                mime_type,
+                params: Vec::new(),
+                text: None,
+            }),
+            None => None,
+        };
+
+        Request {
+            method: req.method().to_string(),
+            url: req.url().to_string(),
+            http_version: format!("{:?}", req.version()),
+            headers: headers(req.headers()),
+            query_string: req
+                .url()
+                .query_pairs()
+                .map(|(name, value)| NameValue {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            cookies: Vec::new(),
+            headers_size: -1,
+            body_size: body.map_or(-1, |body| body.len() as i64),
+            post_data,
+        }
+    }
+}
+
+impl Response {
+    pub fn new(version: Version, status: StatusCode, headers: &HeaderMap, body: &str) -> Response {
+        Response {
+            status: status.as_u16(),
+            status_text: status.canonical_reason().unwrap_or_default().to_string(),
+            http_version: format!("{version:?}"),
+            headers: self::headers(headers),
+            cookies: Vec::new(),
+            content: Content {
+                size: body.len() as i64,
+                mime_type: header(headers, "content-type").unwrap_or_default(),
+                text: Some(body.to_string()),
+            },
+            redirect_url: header(headers, "location").unwrap_or_default(),
+            headers_size: -1,
+            body_size: body.len() as i64,
+        }
+    }
+}
+
+fn headers(headers: &HeaderMap) -> Vec<NameValue> {
+    headers
+        .iter()
+        .map(|(name, value)| NameValue {
+            name: name.to_string(),
+            value: redact::header(name.as_str(), &String::from_utf8_lossy(value.as_bytes()))
+                .to_string(),
+        })
+        .collect()
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers.get(name).and_then(|v| v.to_str().ok()).map(String::from)
+}
+
+/// Convert the entries of a HAR file, optionally only those with a URL containing `filter`, into
+/// request files in a directory per host
+pub fn convert(har: &str, filter: Option<&str>) -> Result<Import, ImportError> {
+    let har: Har = serde_json::from_str(har)
+        .map_err(|e| ImportError::Parse(format!("Invalid HAR file: {e}")))?;
+
+    let mut import = Import::default();
+    let entries = har
+        .log
+        .entries
+        .iter()
+        .filter(|e| filter.map_or(true, |f| e.request.url.contains(f)));
+    for entry in entries {
+        let url: url::Url = match url::Url::parse(&entry.request.url) {
+            Ok(url) => url,
+            Err(e) => {
+                log::warn!("Skipping request with invalid URL {}: {e}", entry.request.url);
+                continue;
+            }
+        };
+
+        let dir: PathBuf = PathBuf::from(import::slug(url.host_str().unwrap_or_default()));
+        let segment: &str = url
+            .path_segments()
+            .and_then(|s| s.rev().find(|s| !s.is_empty()))
+            .unwrap_or("root");
+        let name: String = format!("{} {segment}", entry.request.method.to_lowercase());
+        let comment: String = match &entry.response {
+            Some(response) => format!(
+                "Recorded {} with response {} {}",
+                entry.started_date_time, response.status, response.status_text
+            ),
+            None => format!("Recorded {}", entry.started_date_time),
+        };
+
+        import.request(&dir, &name, Some(&comment), convert_request(&entry.request));
+    }
+
+    Ok(import)
+}
+
+fn convert_request(request: &Request) -> Mapping {
+    let mut mapping = Mapping::new();
+    mapping.insert("method".into(), request.method.to_uppercase().into());
+    mapping.insert("url".into(), request.url.as_str().into());
+
+    let headers: Mapping = request
+        .headers
+        .iter()
+        .filter(|h| !h.name.starts_with(':'))
+        .filter(|h| !SKIPPED_HEADERS.iter().any(|s| h.name.eq_ignore_ascii_case(s)))
+        .map(|h| (h.name.as_str().into(), h.value.as_str().into()))
+        .collect();
+    if !headers.is_empty() {
+        mapping.insert("headers".into(), Value::Mapping(headers));
+    }
+
+    if let Some(post_data) = &request.post_data {
+        let mime_type: &str = &post_data.mime_type;
+        let params = || -> Vec<(String, String)> {
+            post_data
+                .params
+                .iter()
+                .map(|p| (p.name.clone(), p.value.clone().unwrap_or_default()))
+                .collect()
+        };
+        let body: Option<(&str, Value)> = match &post_data.text {
+            _ if mime_type.starts_with("application/x-www-form-urlencoded")
+                && !post_data.params.is_empty() =>
+            {
+                Some(("form", import::params(params())))
+            }
+            Some(text) if !text.is_empty() => Some(import::text_body(text, Some(mime_type))),
+            _ => None,
+        };
+        if let Some((key, value)) = body {
+            mapping.insert(key.into(), value);
+        }
+    }
+
+    mapping
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Version};
+
+    use super::{convert, Entry, Har, Request, Response};
+
+    #[test]
+    fn test_export_and_import_har() {
+        let req = reqwest::blocking::Client::new()
+            .post("https://example.com/api/users?page=2")
+            .header("content-type", "application/json")
+            .header("accept-encoding", "gzip")
+            .body(r#"{"name":"tom"}"#)
+            .build()
+            .unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json"));
+        let response = Response::new(Version::HTTP_11, StatusCode::CREATED, &headers, "{}");
+
+        let started = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        let entry = Entry::new(started, Duration::from_millis(42), Request::from(&req), response);
+        let har: String = Har::new(vec![entry]).to_json();
+        let json: serde_json::Value = serde_json::from_str(&har).unwrap();
+
+        assert_eq!("2020-09-13T12:26:40.000Z", json["log"]["entries"][0]["startedDateTime"]);
+        assert_eq!(42.0, json["log"]["entries"][0]["time"]);
+        assert_eq!("2", json["log"]["entries"][0]["request"]["queryString"][0]["value"]);
+        assert_eq!("Created", json["log"]["entries"][0]["response"]["statusText"]);
+
+        let import = convert(&har, Some("/api/")).unwrap();
+        let doc = &import.requests()[0];
+        assert_eq!(Path::new("example_com/post_users"), doc.path);
+        assert_eq!(doc.request["url"], "https://example.com/api/users?page=2");
+        assert_eq!(doc.request["json"]["name"], "tom");
+        assert!(doc.request["headers"].get("accept-encoding").is_none());
+        assert!(doc
+            .to_yaml()
+            .starts_with("# Recorded 2020-09-13T12:26:40.000Z with response 201"));
+
+        assert!(convert(&har, Some("/other/")).unwrap().requests().is_empty());
+    }
+}
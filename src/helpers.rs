@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use handlebars::{handlebars_helper, Handlebars, RenderError};
+use lazy_static::lazy_static;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::Value;
+
+/// Names of all helpers registered by `register`, which are never mistaken for variables
+pub const NAMES: [&str; 17] = [
+    "uuid",
+    "now",
+    "random_int",
+    "random_string",
+    "base64",
+    "base64_decode",
+    "base64url",
+    "base64url_decode",
+    "urlencode",
+    "sha256",
+    "hmac",
+    "json_escape",
+    "upper",
+    "lower",
+    "default",
+    "exec",
+    "file",
+];
+
+/// Characters that are not percent-encoded by `urlencode`, which are the unreserved characters of
+/// RFC 3986
+const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+lazy_static! {
+    /// Output of commands and content of files read by `exec` and `file` in this run, so that each
+    /// command is only run once
+    static ref EXTERNAL: Mutex<HashMap<String, String>> = Mutex::new(HashMap::new());
+}
+
+/// A failure to run a command with `exec` or to read a file with `file`
+#[derive(Debug)]
+pub struct ExternalError(String);
+
+impl Display for ExternalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ExternalError {}
+
+pub fn register(reg: &mut Handlebars) {
+    reg.register_helper("uuid", Box::new(uuid));
+    reg.register_helper("now", Box::new(now));
+    reg.register_helper("random_int", Box::new(random_int));
+    reg.register_helper("random_string", Box::new(random_string));
+    reg.register_helper("base64", Box::new(base64));
+    reg.register_helper("base64_decode", Box::new(base64_decode));
+    reg.register_helper("base64url", Box::new(base64url));
+    reg.register_helper("base64url_decode", Box::new(base64url_decode));
+    reg.register_helper("urlencode", Box::new(urlencode));
+    reg.register_helper("sha256", Box::new(sha256));
+    reg.register_helper("hmac", Box::new(hmac));
+    reg.register_helper("json_escape", Box::new(json_escape));
+    reg.register_helper("upper", Box::new(upper));
+    reg.register_helper("lower", Box::new(lower));
+    reg.register_helper("default", Box::new(default));
+}
+
+/// Register the `exec` and `file` helpers, which run commands and read files. If they are not
+/// enabled, they are registered so that using them fails with an explanation.
+pub fn register_external(reg: &mut Handlebars, enabled: bool) {
+    if enabled {
+        reg.register_helper("exec", Box::new(exec));
+        reg.register_helper("file", Box::new(file));
+    } else {
+        reg.register_helper("exec", Box::new(disabled));
+        reg.register_helper("file", Box::new(disabled));
+    }
+}
+
+handlebars_helper!(uuid: | | uuid_v4()?);
+handlebars_helper!(now: |{offset: str = ""}, *args| {
+    let format: &str = args.first().and_then(|f| f.as_str()).unwrap_or("rfc3339");
+    format_time(SystemTime::now(), format, offset)?
+});
+handlebars_helper!(random_int: |*args| {
+    let bounds: Vec<i64> = args.iter().map(|v| integer(v)).collect::<Result<_, _>>()?;
+    let (min, max): (i64, i64) = match bounds[..] {
+        [] => (0, 100),
+        [max] => (0, max),
+        [min, max, ..] => (min, max),
+    };
+    if min > max {
+        return Err(RenderError::new(format!("random_int: {min} is greater than {max}")));
+    }
+    let span: u128 = (max as i128 - min as i128) as u128 + 1;
+    let value: u128 = u128::from_le_bytes(random_bytes()?) % span;
+    (min as i128 + value as i128) as i64
+});
+handlebars_helper!(random_string: |*args| {
+    let length: i64 = args.first().map(|v| integer(v)).transpose()?.unwrap_or(16);
+    let bytes: Vec<u8> = random(length.max(0) as usize)?;
+    bytes.iter().map(|b| ALPHANUMERIC[*b as usize % ALPHANUMERIC.len()] as char).collect::<String>()
+});
+handlebars_helper!(base64: |value: str| ::base64::encode(value));
+handlebars_helper!(base64_decode: |value: str| decode(value, ::base64::STANDARD)?);
+handlebars_helper!(base64url: |value: str| ::base64::encode_config(value, ::base64::URL_SAFE_NO_PAD));
+handlebars_helper!(base64url_decode: |value: str| decode(value, ::base64::URL_SAFE_NO_PAD)?);
+handlebars_helper!(urlencode: |value: str| utf8_percent_encode(value, UNRESERVED).to_string());
+handlebars_helper!(sha256: |value: str, {encoding: str = "hex"}| {
+    encode(&openssl::sha::sha256(value.as_bytes()), encoding)?
+});
+handlebars_helper!(hmac: |key: str, message: str, {encoding: str = "hex"}| {
+    encode(&hmac_sha256(key, message).map_err(|e| RenderError::new(e.to_string()))?, encoding)?
+});
+handlebars_helper!(json_escape: |value: str| {
+    let quoted: String = serde_json::to_string(value).unwrap();
+    quoted[1..quoted.len() - 1].to_string()
+});
+handlebars_helper!(upper: |value: str| value.to_uppercase());
+handlebars_helper!(lower: |value: str| value.to_lowercase());
+handlebars_helper!(default: |*args| {
+    let fallback: Value = args.last().map(|v| (*v).clone()).unwrap_or(Value::Null);
+    let value: Option<&&Value> = args.iter().find(|v| !v.is_null() && v.as_str() != Some(""));
+    value.map(|v| (*v).clone()).unwrap_or(fallback)
+});
+handlebars_helper!(exec: |command: str| external(format!("exec {command}"), || run(command))?);
+handlebars_helper!(file: |path: str| external(format!("file {path}"), || read(path))?);
+handlebars_helper!(disabled: |*_args| {
+    let msg = "The exec and file helpers are disabled, enable them with --allow-exec";
+    Err::<String, _>(RenderError::from_error(msg, ExternalError(String::from(msg))))?
+});
+
+fn integer(value: &Value) -> Result<i64, RenderError> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+    .ok_or_else(|| RenderError::new(format!("{value} is not an integer")))
+}
+
+/// The cached result of `f` for `key`, or otherwise the result of `f`, which is cached if successful
+fn external(
+    key: String,
+    f: impl Fn() -> Result<String, ExternalError>,
+) -> Result<String, RenderError> {
+    if let Some(value) = EXTERNAL.lock().unwrap().get(&key) {
+        return Ok(value.clone());
+    }
+    let value: String = f().map_err(|e| RenderError::from_error(&e.to_string(), e))?;
+    EXTERNAL.lock().unwrap().insert(key, value.clone());
+    Ok(value)
+}
+
+/// Run a command in a shell and return its output, without trailing line breaks
+fn run(command: &str) -> Result<String, ExternalError> {
+    log::info!("Running command {command:?}");
+    let output: Output = shell(command)
+        .output()
+        .map_err(|e| ExternalError(format!("Unable to run command {command:?}: {e}")))?;
+
+    if !output.status.success() {
+        let stderr: String = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        let msg: String = format!("Command {command:?} failed with {}", output.status);
+        return Err(ExternalError(match stderr.is_empty() {
+            true => msg,
+            false => format!("{msg}: {stderr}"),
+        }));
+    }
+
+    String::from_utf8(output.stdout)
+        .map(|stdout| stdout.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|_| ExternalError(format!("Output of command {command:?} is not valid UTF-8")))
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.arg("-c").arg(command);
+    shell
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.arg("/C").arg(command);
+    shell
+}
+
+/// Read a file, where a leading `~` is the home directory, and return its content without trailing
+/// line breaks
+fn read(path: &str) -> Result<String, ExternalError> {
+    let home = || std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+    let resolved: PathBuf = match (path.strip_prefix("~/"), home()) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    };
+    std::fs::read_to_string(&resolved)
+        .map(|content| content.trim_end_matches(['\r', '\n']).to_string())
+        .map_err(|e| ExternalError(format!("Unable to read file {path:?}: {e}")))
+}
+
+fn random(len: usize) -> Result<Vec<u8>, RenderError> {
+    let mut bytes: Vec<u8> = vec![0; len];
+    openssl::rand::rand_bytes(&mut bytes).map_err(|e| RenderError::new(e.to_string()))?;
+    Ok(bytes)
+}
+
+fn random_bytes() -> Result<[u8; 16], RenderError> {
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&random(16)?);
+    Ok(bytes)
+}
+
+/// A random version 4 UUID
+fn uuid_v4() -> Result<String, RenderError> {
+    let mut bytes: [u8; 16] = random_bytes()?;
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    Ok(format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    ))
+}
+
+fn decode(value: &str, config: ::base64::Config) -> Result<String, RenderError> {
+    let bytes: Vec<u8> = ::base64::decode_config(value.trim(), config)
+        .map_err(|e| RenderError::new(format!("Invalid base64 {value:?}: {e}")))?;
+    String::from_utf8(bytes)
+        .map_err(|_| RenderError::new(format!("{value:?} is not base64 encoded text")))
+}
+
+fn encode(bytes: &[u8], encoding: &str) -> Result<String, RenderError> {
+    match encoding {
+        "hex" => Ok(bytes.iter().map(|b| format!("{b:02x}")).collect()),
+        "base64" => Ok(::base64::encode(bytes)),
+        "base64url" => Ok(::base64::encode_config(bytes, ::base64::URL_SAFE_NO_PAD)),
+        _ => Err(RenderError::new(format!(
+            "Unknown encoding {encoding:?}, use hex, base64 or base64url"
+        ))),
+    }
+}
+
+fn hmac_sha256(key: &str, message: &str) -> Result<Vec<u8>, openssl::error::ErrorStack> {
+    let key = PKey::hmac(key.as_bytes())?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(message.as_bytes())?;
+    signer.sign_to_vec()
+}
+
+/// Format a point in time, in UTC, moved by `offset` which is a duration like `-1h` or `30m`.
+/// The format is either `rfc3339`, `epoch` (seconds), `epoch_millis` or a strftime format.
+fn format_time(time: SystemTime, format: &str, offset: &str) -> Result<String, RenderError> {
+    let time: SystemTime = match offset.strip_prefix('-') {
+        _ if offset.is_empty() => time,
+        Some(offset) => time - duration(offset)?,
+        None => time + duration(offset.trim_start_matches('+'))?,
+    };
+    let since_epoch: Duration = time
+        .duration_since(UNIX_EPOCH)
+        .map_err(|_| RenderError::new("Time is before 1970-01-01"))?;
+
+    match format {
+        "rfc3339" => Ok(humantime::format_rfc3339_seconds(time).to_string()),
+        "rfc3339_millis" => Ok(humantime::format_rfc3339_millis(time).to_string()),
+        "epoch" => Ok(since_epoch.as_secs().to_string()),
+        "epoch_millis" => Ok(since_epoch.as_millis().to_string()),
+        format => strftime(since_epoch, format),
+    }
+}
+
+fn duration(offset: &str) -> Result<Duration, RenderError> {
+    humantime::parse_duration(offset)
+        .map_err(|e| RenderError::new(format!("Invalid offset {offset:?}: {e}")))
+}
+
+/// Format a time in UTC, given as the duration since the Unix epoch, with the most common
+/// conversion specifications of strftime
+fn strftime(since_epoch: Duration, format: &str) -> Result<String, RenderError> {
+    let secs: u64 = since_epoch.as_secs();
+    let days: i64 = (secs / 86_400) as i64;
+    let (year, month, day) = civil(days);
+    let (hour, minute, second) = (secs % 86_400 / 3600, secs % 3600 / 60, secs % 60);
+    let weekday: usize = (days + 3).rem_euclid(7) as usize;
+    let month_name: &str = MONTHS[month as usize - 1];
+
+    let mut output = String::with_capacity(format.len() * 2);
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        let spec: String = match chars.next() {
+            Some('Y') => year.to_string(),
+            Some('y') => format!("{:02}", year % 100),
+            Some('m') => format!("{month:02}"),
+            Some('d') => format!("{day:02}"),
+            Some('e') => format!("{day:>2}"),
+            Some('H') => format!("{hour:02}"),
+            Some('I') => format!("{:02}", (hour + 11) % 12 + 1),
+            Some('p') => String::from(if hour < 12 { "AM" } else { "PM" }),
+            Some('M') => format!("{minute:02}"),
+            Some('S') => format!("{second:02}"),
+            Some('f') => format!("{:03}", since_epoch.subsec_millis()),
+            Some('j') => format!("{:03}", days - civil_days(year, 1, 1) + 1),
+            Some('a') => WEEKDAYS[weekday][..3].to_string(),
+            Some('A') => WEEKDAYS[weekday].to_string(),
+            Some('u') => (weekday + 1).to_string(),
+            Some('b') => month_name[..3].to_string(),
+            Some('B') => month_name.to_string(),
+            Some('F') => format!("{year}-{month:02}-{day:02}"),
+            Some('T') => format!("{hour:02}:{minute:02}:{second:02}"),
+            Some('s') => secs.to_string(),
+            Some('z') => String::from("+0000"),
+            Some('Z') => String::from("UTC"),
+            Some('%') => String::from("%"),
+            Some(c) => return Err(RenderError::new(format!("Unsupported time format %{c}"))),
+            None => return Err(RenderError::new("Time format ends with %")),
+        };
+        output.push_str(&spec);
+    }
+
+    Ok(output)
+}
+
+/// The date of a day, counted from 1970-01-01
+fn civil(days: i64) -> (i64, u32, u32) {
+    let z: i64 = days + 719_468;
+    let era: i64 = z.div_euclid(146_097);
+    let doe: i64 = z.rem_euclid(146_097);
+    let yoe: i64 = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy: i64 = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp: i64 = (5 * doy + 2) / 153;
+    let day: u32 = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month: u32 = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year: i64 = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The number of days from 1970-01-01 to a date
+fn civil_days(year: i64, month: u32, day: u32) -> i64 {
+    let year: i64 = if month <= 2 { year - 1 } else { year };
+    let era: i64 = year.div_euclid(400);
+    let yoe: i64 = year.rem_euclid(400);
+    let mp: i64 = (month as i64 + 9) % 12;
+    let doy: i64 = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe: i64 = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use handlebars::{no_escape, Handlebars};
+    use serde_json::json;
+
+    use super::{format_time, register};
+
+    #[test]
+    fn test_helpers() {
+        let mut reg = Handlebars::new();
+        reg.register_escape_fn(no_escape);
+        reg.set_strict_mode(true);
+        register(&mut reg);
+        let vars = json!({"name": "Tom & Jerry", "empty": "", "secret": "key", "text": "a\"b\n"});
+        let render = |template: &str| reg.render_template(template, &vars).unwrap();
+
+        assert_eq!("VG9tICYgSmVycnk=", render("{{base64 name}}"));
+        assert_eq!("Tom & Jerry", render("{{base64_decode (base64 name)}}"));
+        assert_eq!("Tom%20%26%20Jerry", render("{{urlencode name}}"));
+        assert_eq!("TOM & JERRY", render("{{upper name}}"));
+        assert_eq!("fallback", render("{{default missing empty \"fallback\"}}"));
+        assert_eq!("a\\\"b\\n", render("{{json_escape text}}"));
+        assert_eq!(
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+            render("{{sha256 \"test\"}}")
+        );
+        assert_eq!(
+            "97yD9DBThCSxMpjmqm+xQ+9NWaFJRhdZl0edvC0aPNg=",
+            render("{{hmac secret \"The quick brown fox jumps over the lazy dog\" encoding=\"base64\"}}")
+        );
+
+        let uuid: String = render("{{uuid}}");
+        assert_eq!(36, uuid.len());
+        assert_eq!(Some('4'), uuid.chars().nth(14));
+        assert_eq!(12, render("{{random_string 12}}").len());
+        let int: i64 = render("{{random_int 5 7}}").parse().unwrap();
+        assert!((5..=7).contains(&int));
+
+        assert!(reg.render_template("{{upper missing}}", &vars).is_err());
+    }
+
+    #[test]
+    fn test_format_time() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_709_210_096_789);
+        let format = |format: &str, offset: &str| format_time(time, format, offset).unwrap();
+
+        assert_eq!("2024-02-29T12:34:56Z", format("rfc3339", ""));
+        assert_eq!("1709213696", format("epoch", "+1h"));
+        assert_eq!("1709210036789", format("epoch_millis", "-1m"));
+        assert_eq!("Thu, 29 Feb 2024 12:34:56 +0000", format("%a, %d %b %Y %T %z", ""));
+        assert_eq!("061 2024-03-01 00:34:56.789", format("%j %F %H:%M:%S.%f", "12h"));
+    }
+}
@@ -1,4 +1,9 @@
-use std::{collections::HashMap, fmt::Display, str::FromStr};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use reqwest::{
     header::{HeaderMap, HeaderName, HeaderValue},
@@ -6,21 +11,41 @@ use reqwest::{
 };
 use serde::Deserialize;
 
+use crate::body::{BodyFile, Multipart};
+use crate::capture::Capture;
+use crate::expect::Expectations;
 use crate::headers::Appendable;
+use crate::params::Params;
 
 #[derive(Debug, Deserialize)]
 pub struct HttpRequest {
     #[serde(alias = "method")]
     verb: Verb,
     url: String,
+    #[serde(default)]
+    query: Params,
     body: Option<String>,
+    json: Option<serde_json::Value>,
+    form: Option<Params>,
+    multipart: Option<Multipart>,
+    body_file: Option<BodyFile>,
+    #[serde(skip)]
+    content: Option<Vec<u8>>,
     headers: Option<HashMap<String, String>>,
+    #[serde(default)]
+    capture: BTreeMap<String, Capture>,
+    #[serde(default)]
+    expect: Expectations,
+    openapi: Option<PathBuf>,
 }
 
 const USER_AGENT_KEY: &str = "user-agent";
 const USER_AGENT: &str = "fire/0.1.0";
 const CONTENT_LENGTH_KEY: &str = "content-length";
 const HOST_KEY: &str = "host";
+const CONTENT_TYPE_KEY: &str = "content-type";
+const CONTENT_TYPE_JSON: &str = "application/json";
+const CONTENT_TYPE_FORM: &str = "application/x-www-form-urlencoded";
 
 impl HttpRequest {
     pub fn verb(&self) -> Verb {
@@ -28,11 +53,17 @@ impl HttpRequest {
     }
 
     pub fn url(&self) -> Result<Url, url::ParseError> {
-        if self.url.starts_with("http://") || self.url.starts_with("https://") {
-            Url::parse(&self.url)
+        let mut url: Url = if self.url.starts_with("http://") || self.url.starts_with("https://") {
+            Url::parse(&self.url)?
         } else {
-            Url::parse(&format!("https://{}", &self.url))
+            Url::parse(&format!("https://{}", &self.url))?
+        };
+
+        if !self.query.is_empty() {
+            url.query_pairs_mut().extend_pairs(self.query.iter());
         }
+
+        Ok(url)
     }
 
     pub fn headers(&self) -> HeaderMap<HeaderValue> {
@@ -47,9 +78,20 @@ impl HttpRequest {
             headers.put_if_absent(HOST_KEY, host);
         }
 
-        let body_size: String = self.body_size().to_string();
+        if self.json.is_some() {
+            headers.put_if_absent(CONTENT_TYPE_KEY, CONTENT_TYPE_JSON);
+        } else if self.form.is_some() {
+            headers.put_if_absent(CONTENT_TYPE_KEY, CONTENT_TYPE_FORM);
+        }
+
         headers.put_if_absent(USER_AGENT_KEY, USER_AGENT);
-        headers.put_if_absent(CONTENT_LENGTH_KEY, body_size);
+
+        // Content type and length of a multipart body is set when the form is built
+        if self.multipart.is_none() {
+            let body_size: String = self.body_size().to_string();
+            headers.put_if_absent(CONTENT_LENGTH_KEY, body_size);
+        }
+
         headers
     }
 
@@ -59,17 +101,54 @@ impl HttpRequest {
         (k, v)
     }
 
-    pub fn has_body(&self) -> bool {
-        self.body_size() != 0
+    pub fn body(&self) -> Option<Vec<u8>> {
+        if let Some(content) = &self.content {
+            return Some(content.clone());
+        }
+
+        if let Some(json) = &self.json {
+            return Some(serde_json::to_vec(json).unwrap());
+        }
+
+        if let Some(form) = &self.form {
+            let form: String = url::form_urlencoded::Serializer::new(String::new())
+                .extend_pairs(form.iter())
+                .finish();
+            return Some(form.into_bytes());
+        }
+
+        self.body.clone().map(String::into_bytes)
     }
 
-    pub fn body(&self) -> &Option<String> {
-        &self.body
+    pub fn captures(&self) -> &BTreeMap<String, Capture> {
+        &self.capture
+    }
+
+    pub fn expectations(&self) -> &Expectations {
+        &self.expect
+    }
+
+    /// OpenAPI document to validate the response against, relative to the request file
+    pub fn openapi(&self) -> Option<&Path> {
+        self.openapi.as_deref()
+    }
+
+    pub fn body_file(&self) -> Option<&BodyFile> {
+        self.body_file.as_ref()
+    }
+
+    /// Set the content of the body, once it has been read from the body file
+    pub fn set_content(&mut self, content: Vec<u8>) {
+        self.content = Some(content);
+    }
+
+    pub fn multipart(&self) -> Option<&Multipart> {
+        self.multipart.as_ref()
     }
 
     pub fn body_size(&self) -> usize {
         match self.verb {
-            Verb::Post | Verb::Put | Verb::Delete | Verb::Patch => match &self.body {
+            Verb::Post | Verb::Put | Verb::Delete | Verb::Patch => match self.body() {
                 Some(b) => b.len(),
                 None => 0,
             },
@@ -82,7 +161,32 @@ impl FromStr for HttpRequest {
     type Err = serde_yaml::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        serde_yaml::from_str(s)
+        HttpRequest::try_from(serde_yaml::from_str::<serde_yaml::Value>(s)?)
+    }
+}
+
+impl TryFrom<serde_yaml::Value> for HttpRequest {
+    type Error = serde_yaml::Error;
+
+    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+        let request: HttpRequest = serde_yaml::from_value(value)?;
+        let bodies: usize = [
+            request.body.is_some(),
+            request.json.is_some(),
+            request.form.is_some(),
+            request.multipart.is_some(),
+            request.body_file.is_some(),
+        ]
+        .into_iter()
+        .filter(|present| *present)
+        .count();
+
+        if bodies > 1 {
+            return Err(serde::de::Error::custom(
+                "fields `body`, `json`, `form`, `multipart` and `body_file` are mutually exclusive",
+            ));
+        }
+        Ok(request)
     }
 }
 
@@ -212,4 +316,61 @@ mod tests {
 
         assert!(request.body().is_some())
     }
+
+    #[test]
+    fn test_query_is_encoded_and_merged_into_url() {
+        let input = r###"
+            method: GET
+            url: https://example.com/search?lang=en
+            query:
+              q: Tom & Jerry
+              tag: [a b, "ö"]
+        "###;
+
+        let request = HttpRequest::from_str(input).unwrap();
+        let url = request.url().unwrap();
+
+        assert_eq!("lang=en&q=Tom+%26+Jerry&tag=a+b&tag=%C3%B6", url.query().unwrap());
+    }
+
+    #[test]
+    fn test_json_body_is_serialized_with_content_type() {
+        let input = r###"
+            method: POST
+            url: https://example.com/users
+            json:
+              name: Tom "Cat"
+              roles: [admin]
+              age: 3
+        "###;
+
+        let request = HttpRequest::from_str(input).unwrap();
+        let headers: HeaderMap<HeaderValue> = request.headers();
+
+        assert_eq!("application/json", headers.get("content-type").unwrap());
+        assert_eq!(
+            r#"{"age":3,"name":"Tom \"Cat\"","roles":["admin"]}"#,
+            String::from_utf8(request.body().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_form_body_is_encoded_with_content_type() {
+        let input = r###"
+            method: POST
+            url: https://example.com/login
+            form:
+              user: tom & jerry
+              scope: [read, write]
+        "###;
+
+        let request = HttpRequest::from_str(input).unwrap();
+        let headers: HeaderMap<HeaderValue> = request.headers();
+
+        assert_eq!("application/x-www-form-urlencoded", headers.get("content-type").unwrap());
+        assert_eq!(
+            "user=tom+%26+jerry&scope=read&scope=write",
+            String::from_utf8(request.body().unwrap()).unwrap()
+        );
+    }
 }
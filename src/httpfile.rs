@@ -0,0 +1,235 @@
+use serde_yaml::{Mapping, Value};
+
+/// Extensions of files in the format of the JetBrains HTTP Client and the VS Code REST Client
+pub const EXTENSIONS: [&str; 2] = ["http", "rest"];
+
+const SEPARATOR: &str = "###";
+const NAME_DIRECTIVE: &str = "@name";
+const METHODS: [&str; 9] = [
+    "GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH",
+];
+
+/// The requests and file variables (`@name = value`) of a `.http` file
+#[derive(Debug, Default)]
+pub struct HttpFile {
+    pub requests: Vec<HttpBlock>,
+    pub variables: Vec<(String, String)>,
+}
+
+/// A request in a `.http` file, from its request line to the next `###` separator
+#[derive(Debug)]
+pub struct HttpBlock {
+    pub name: Option<String>,
+    pub comment: Option<String>,
+    pub template: String,
+}
+
+/// Split a `.http` file into its requests, which are separated by lines starting with `###`.
+/// Comments (`#` or `//`) and variables before the request line of each request are removed from
+/// its template, and a comment in the format `# @name login` names the request.
+pub fn split(input: &str) -> HttpFile {
+    let mut file = HttpFile::default();
+    for block in blocks(input) {
+        let mut name: Option<String> = None;
+        let mut comment: Vec<&str> = block.comment.into_iter().collect();
+        let mut lines = block.lines.into_iter().skip_while(|line| {
+            let line: &str = line.trim();
+            if let Some(text) = strip_comment(line) {
+                match text.strip_prefix(NAME_DIRECTIVE) {
+                    Some(value) => name = Some(value.trim().to_string()),
+                    None if !text.is_empty() => comment.push(text),
+                    None => {}
+                }
+                true
+            } else if let Some(variable) = variable(line) {
+                file.variables.push(variable);
+                true
+            } else {
+                line.is_empty()
+            }
+        });
+
+        let template: Vec<&str> = lines.by_ref().collect();
+        if template.is_empty() {
+            continue;
+        }
+
+        file.requests.push(HttpBlock {
+            name,
+            comment: if comment.is_empty() { None } else { Some(comment.join(" ")) },
+            template: template.join("\n"),
+        });
+    }
+    file
+}
+
+struct Block<'a> {
+    comment: Option<&'a str>,
+    lines: Vec<&'a str>,
+}
+
+fn blocks(input: &str) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = vec![Block {
+        comment: None,
+        lines: Vec::new(),
+    }];
+    for line in input.lines() {
+        match line.strip_prefix(SEPARATOR) {
+            Some(comment) => blocks.push(Block {
+                comment: Some(comment.trim_start_matches('#').trim()).filter(|c| !c.is_empty()),
+                lines: Vec::new(),
+            }),
+            None => blocks.last_mut().unwrap().lines.push(line),
+        }
+    }
+    blocks
+}
+
+fn strip_comment(line: &str) -> Option<&str> {
+    line.strip_prefix('#').or_else(|| line.strip_prefix("//")).map(str::trim)
+}
+
+/// Parse a file variable declaration, `@name = value`
+fn variable(line: &str) -> Option<(String, String)> {
+    let (name, value) = line.strip_prefix('@')?.split_once('=')?;
+    let name: &str = name.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((name.to_string(), value.trim().to_string()))
+}
+
+/// Convert a request in the `.http` format, with all variables already substituted, into a
+/// request in the format of a request file.
+///
+/// The request consists of a request line (`POST https://example.com HTTP/1.1`, where the method
+/// and HTTP version are optional), optionally followed by lines continuing the query string (`?` or
+/// `&`), then headers, and after an empty line the body. A body consisting only of `< path` is read
+/// from the file, and with `<@ path` variables in the file are substituted as well.
+pub fn to_request(input: &str) -> Result<Mapping, String> {
+    let mut lines = input.lines().peekable();
+    let request_line: &str = loop {
+        match lines.next().map(str::trim) {
+            Some(line) if line.is_empty() || strip_comment(line).is_some() => continue,
+            Some(line) => break line,
+            None => return Err(String::from("No request line found")),
+        }
+    };
+
+    let mut parts: Vec<&str> = request_line.split_whitespace().collect();
+    if parts.len() > 1 && parts.last().unwrap().starts_with("HTTP/") {
+        parts.pop();
+    }
+    let (method, url): (&str, String) = match parts.as_slice() {
+        [url] => ("GET", url.to_string()),
+        [method, url] if METHODS.contains(method) => (method, url.to_string()),
+        _ => return Err(format!("Invalid request line: {request_line}")),
+    };
+
+    let mut url: String = url;
+    while let Some(line) = lines.next_if(|line| line.trim().starts_with(['?', '&'])) {
+        url.push_str(line.trim());
+    }
+
+    let mut headers = Mapping::new();
+    for line in lines.by_ref() {
+        let line: &str = line.trim();
+        if line.is_empty() {
+            break;
+        } else if strip_comment(line).is_some() {
+            continue;
+        }
+        let (name, value) =
+            line.split_once(':').ok_or_else(|| format!("Invalid header: {line}"))?;
+        headers.insert(name.trim().into(), value.trim().into());
+    }
+
+    let body: Vec<&str> = lines
+        .take_while(|line| !line.starts_with("> {%") && !line.starts_with("<> "))
+        .collect();
+    let body: String = body.join("\n").trim_end().to_string();
+
+    let mut request = Mapping::new();
+    request.insert("method".into(), method.into());
+    request.insert("url".into(), url.into());
+    if !headers.is_empty() {
+        request.insert("headers".into(), Value::Mapping(headers));
+    }
+
+    if let Some(path) = body.strip_prefix("<@").filter(|path| !path.contains('\n')) {
+        let mut body_file = Mapping::new();
+        body_file.insert("path".into(), path.trim().into());
+        body_file.insert("template".into(), true.into());
+        request.insert("body_file".into(), Value::Mapping(body_file));
+    } else if let Some(path) = body.strip_prefix("< ").filter(|path| !path.contains('\n')) {
+        request.insert("body_file".into(), path.trim().into());
+    } else if !body.is_empty() {
+        request.insert("body".into(), body.as_str().into());
+    }
+
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use reqwest::header::HeaderMap;
+
+    use crate::document::{DocumentError, Entry, RequestFile};
+    use crate::prop::{Property, Source};
+    use crate::template::Options;
+
+    #[test]
+    fn test_parse_http_file() -> Result<(), DocumentError> {
+        let input = r###"
+@host = example.com
+@base = https://{{host}}/api
+
+### Log in
+# @name login
+POST {{base}}/login HTTP/1.1
+Content-Type: application/json
+// A comment
+Accept: application/json
+
+{
+  "user": "{{USER}}"
+}
+
+> {% client.global.set("token", response.body.token); %}
+
+###
+GET {{base}}/users
+    ?page=2
+    &size=10
+
+### Upload
+PUT https://example.com/avatar
+Content-Type: image/png
+
+< ./avatar.png
+"###;
+
+        let file = RequestFile::parse_file(Path::new("api.http"), input);
+        let labels: Vec<String> = file.entries().iter().map(Entry::label).collect();
+        assert_eq!(vec!["login", "2", "3"], labels);
+        assert_eq!(Some("Log in"), file.entries()[0].comment());
+
+        let host: Property = "host=other.com".parse().unwrap();
+        let vars = vec!["USER=tom".parse().unwrap(), host.with_source(Source::Arg)];
+        let login = file.entries()[0].render(vars.clone(), &Options::default())?;
+        let headers: HeaderMap = login.headers();
+        assert_eq!("https://other.com/api/login", login.url().unwrap().as_str());
+        assert_eq!("application/json", headers.get("accept").unwrap());
+        assert_eq!(b"{\n  \"user\": \"tom\"\n}".to_vec(), login.body().unwrap());
+
+        let users = file.entries()[1].render(vars.clone(), &Options::default())?;
+        assert_eq!("https://other.com/api/users?page=2&size=10", users.url().unwrap().as_str());
+
+        let upload = file.entries()[2].render(vars, &Options::default())?;
+        assert_eq!(Path::new("./avatar.png"), upload.body_file().unwrap().path());
+
+        Ok(())
+    }
+}
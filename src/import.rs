@@ -0,0 +1,227 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde_yaml::{Mapping, Value};
+
+const EXTENSION: &str = "yml";
+
+lazy_static! {
+    static ref VARIABLE: Regex = Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}").unwrap();
+}
+
+/// Request files and environment files converted from another format, which are written to a
+/// directory by [`Import::write`]
+#[derive(Debug, Default)]
+pub struct Import {
+    requests: Vec<RequestDoc>,
+    envs: Vec<EnvFile>,
+}
+
+/// A request file, at a path relative to the output directory without its extension
+#[derive(Debug)]
+pub struct RequestDoc {
+    pub path: PathBuf,
+    pub comment: Option<String>,
+    pub request: Mapping,
+}
+
+/// An environment file, such as `.env` or `staging.env`
+#[derive(Debug)]
+pub struct EnvFile {
+    pub path: PathBuf,
+    pub vars: Vec<(String, String)>,
+}
+
+#[derive(Debug)]
+pub enum ImportError {
+    File(PathBuf, std::io::Error),
+    Exists(PathBuf),
+    Parse(String),
+}
+
+impl Import {
+    /// Add a request, in the directory `dir` (relative to the output directory), with a file name
+    /// derived from `name` that is unique within that directory
+    pub fn request(&mut self, dir: &Path, name: &str, comment: Option<&str>, request: Mapping) {
+        let base: String = slug(name);
+        let taken: HashSet<&Path> = self.requests.iter().map(|r| r.path.as_path()).collect();
+        let path: PathBuf = (1..)
+            .map(|n| match n {
+                1 => dir.join(&base),
+                n => dir.join(format!("{base}_{n}")),
+            })
+            .find(|path| !taken.contains(path.as_path()))
+            .unwrap();
+
+        let comment: Option<String> = comment
+            .and_then(|c| c.lines().map(str::trim).find(|line| !line.is_empty()))
+            .map(String::from);
+
+        self.requests.push(RequestDoc {
+            path,
+            comment,
+            request,
+        });
+    }
+
+    /// Add an environment file with variables, skipping variables that can not be represented in
+    /// an environment file
+    pub fn env(&mut self, path: PathBuf, vars: Vec<(String, String)>) {
+        let vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(key, value)| {
+                let valid: bool = !value.contains('\n');
+                if !valid {
+                    log::warn!("Skipping variable {key} with a value on several lines");
+                }
+                valid
+            })
+            .map(|(key, value)| (variable_name(&key), value))
+            .collect();
+
+        if !vars.is_empty() {
+            self.envs.push(EnvFile { path, vars });
+        }
+    }
+
+    pub fn requests(&self) -> &[RequestDoc] {
+        &self.requests
+    }
+
+    pub fn envs(&self) -> &[EnvFile] {
+        &self.envs
+    }
+
+    /// Write all files to the directory `dir`, returning the paths of all written files. Existing
+    /// files are only overwritten if `force` is set.
+    pub fn write(&self, dir: &Path, force: bool) -> Result<Vec<PathBuf>, ImportError> {
+        let files: Vec<(PathBuf, String)> = self
+            .requests
+            .iter()
+            .map(|r| (dir.join(r.path.with_extension(EXTENSION)), r.to_yaml()))
+            .chain(self.envs.iter().map(|e| (dir.join(&e.path), e.content())))
+            .collect();
+
+        if !force {
+            if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+                return Err(ImportError::Exists(path.clone()));
+            }
+        }
+
+        for (path, content) in &files {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| ImportError::File(parent.to_path_buf(), e))?;
+            }
+            std::fs::write(path, content).map_err(|e| ImportError::File(path.clone(), e))?;
+        }
+
+        Ok(files.into_iter().map(|(path, _)| path).collect())
+    }
+}
+
+impl RequestDoc {
+    /// The request file, with the comment (if any) as the first line
+    pub fn to_yaml(&self) -> String {
+        let yaml: String = serde_yaml::to_string(&self.request).unwrap();
+        match &self.comment {
+            Some(comment) => format!("# {comment}\n{yaml}"),
+            None => yaml,
+        }
+    }
+}
+
+impl EnvFile {
+    fn content(&self) -> String {
+        self.vars.iter().map(|(key, value)| format!("{key}={value}\n")).collect()
+    }
+}
+
+/// A name that is safe to use as a file or directory name
+pub fn slug(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect::<String>()
+        .split('_')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("_");
+
+    if slug.is_empty() {
+        String::from("request")
+    } else {
+        slug
+    }
+}
+
+/// A variable name that can be used in a template, where characters that are not allowed in a
+/// variable name are replaced with `_`
+pub fn variable_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| if c.is_whitespace() || "{}[]'\"/".contains(c) { '_' } else { c })
+        .collect()
+}
+
+/// Convert all variables (`{{ name }}`) in a string into template variables, where the name is
+/// passed through `rename` before it is made a valid variable name
+pub fn variables(input: &str, rename: impl Fn(&str) -> &str) -> String {
+    VARIABLE
+        .replace_all(input, |caps: &Captures| {
+            let name: &str = rename(&caps[1]);
+            if name.starts_with('$') {
+                log::warn!("Dynamic variable {name} is not supported and must be replaced");
+            }
+            format!("{{{{{}}}}}", variable_name(name))
+        })
+        .to_string()
+}
+
+/// Parameters as a mapping, where a parameter that occurs several times has a list of values
+pub fn params(pairs: Vec<(String, String)>) -> Value {
+    let mut params = Mapping::new();
+    for (key, value) in pairs {
+        let key = Value::from(key);
+        match params.get_mut(&key) {
+            Some(Value::Sequence(values)) => values.push(value.into()),
+            Some(existing) => *existing = Value::Sequence(vec![existing.clone(), value.into()]),
+            None => {
+                params.insert(key, value.into());
+            }
+        }
+    }
+    Value::Mapping(params)
+}
+
+/// The key and value of a body with the content `text`, which is a `json` section if the content is
+/// JSON, or otherwise a plain `body`
+pub fn text_body(text: &str, content_type: Option<&str>) -> (&'static str, Value) {
+    let json: bool = content_type.map_or(true, |ct| ct.contains("json"));
+    if json {
+        let parsed: Result<serde_json::Value, _> = serde_json::from_str(text);
+        if let Ok(json @ (serde_json::Value::Object(_) | serde_json::Value::Array(_))) = parsed {
+            return ("json", serde_yaml::to_value(json).unwrap());
+        }
+    }
+    ("body", text.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{slug, variables};
+
+    #[test]
+    fn test_slug_and_variables() {
+        assert_eq!("get_user_by_id", slug("Get user (by ID)"));
+        assert_eq!("request", slug("  "));
+        assert_eq!(
+            "{{base_url}}/users/{{user_id}}",
+            variables("{{ _.base_url }}/users/{{user id}}", |n| n.trim_start_matches("_."))
+        );
+    }
+}
@@ -0,0 +1,337 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::import::{self, Import, ImportError};
+
+/// An export from Insomnia, in version 4 of its export format
+#[derive(Debug, Deserialize)]
+struct Export {
+    resources: Vec<Resource>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "_type", rename_all = "snake_case")]
+enum Resource {
+    Workspace {
+        #[serde(rename = "_id")]
+        id: String,
+    },
+    RequestGroup {
+        #[serde(rename = "_id")]
+        id: String,
+        #[serde(rename = "parentId")]
+        parent: Option<String>,
+        name: String,
+    },
+    Request(Box<Request>),
+    Environment {
+        #[serde(rename = "parentId")]
+        parent: Option<String>,
+        name: String,
+        #[serde(default)]
+        data: serde_json::Map<String, serde_json::Value>,
+    },
+    #[serde(other)]
+    Other,
+}
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    #[serde(rename = "parentId")]
+    parent: Option<String>,
+    name: String,
+    description: Option<String>,
+    method: Option<String>,
+    url: String,
+    body: Option<Body>,
+    #[serde(default)]
+    headers: Vec<Param>,
+    #[serde(default)]
+    parameters: Vec<Param>,
+    authentication: Option<Auth>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Body {
+    mime_type: Option<String>,
+    text: Option<String>,
+    #[serde(default)]
+    params: Vec<Param>,
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Param {
+    name: String,
+    #[serde(default)]
+    value: String,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    file_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Auth {
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    disabled: bool,
+    token: Option<String>,
+    prefix: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    key: Option<String>,
+    value: Option<String>,
+    add_to: Option<String>,
+}
+
+/// Convert an Insomnia export into request files in a directory per folder. The variables of the
+/// base environment are written to `.env`, and the variables of each sub environment to
+/// `<environment>.env`.
+pub fn convert(export: &str) -> Result<Import, ImportError> {
+    let export: Export = serde_json::from_str(export)
+        .map_err(|e| ImportError::Parse(format!("Invalid Insomnia export: {e}")))?;
+
+    let workspaces: Vec<&str> = export
+        .resources
+        .iter()
+        .filter_map(|r| match r {
+            Resource::Workspace { id } => Some(id.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let groups: HashMap<&str, (Option<&str>, &str)> = export
+        .resources
+        .iter()
+        .filter_map(|r| match r {
+            Resource::RequestGroup { id, parent, name } => {
+                Some((id.as_str(), (parent.as_deref(), name.as_str())))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut import = Import::default();
+    for resource in &export.resources {
+        match resource {
+            Resource::Request(request) => {
+                let dir: PathBuf = directory(&groups, request.parent.as_deref());
+                let comment: Option<&str> = request.description.as_deref();
+                import.request(&dir, &request.name, comment, convert_request(request));
+            }
+            Resource::Environment { parent, name, data } => {
+                let base: bool = parent.as_deref().map_or(true, |p| workspaces.contains(&p));
+                let path: String =
+                    if base { String::from(".env") } else { format!("{}.env", import::slug(name)) };
+                let mut vars: Vec<(String, String)> = Vec::new();
+                flatten("", data, &mut vars);
+                import.env(PathBuf::from(path), vars);
+            }
+            _ => {}
+        }
+    }
+
+    Ok(import)
+}
+
+/// The directory of a request, with a subdirectory for each folder the request is in
+fn directory(groups: &HashMap<&str, (Option<&str>, &str)>, parent: Option<&str>) -> PathBuf {
+    let mut names: Vec<String> = Vec::new();
+    let mut parent: Option<&str> = parent;
+    while let Some((grandparent, name)) = parent.and_then(|id| groups.get(id)) {
+        names.push(import::slug(name));
+        parent = *grandparent;
+    }
+    names.into_iter().rev().collect()
+}
+
+/// Flatten nested environment data into variables with dotted names, like `api.url`
+fn flatten(
+    prefix: &str,
+    data: &serde_json::Map<String, serde_json::Value>,
+    vars: &mut Vec<(String, String)>,
+) {
+    for (key, value) in data {
+        let key: String = format!("{prefix}{key}");
+        match value {
+            serde_json::Value::Object(nested) => flatten(&format!("{key}."), nested, vars),
+            serde_json::Value::String(s) => vars.push((key, template(s))),
+            other => vars.push((key, other.to_string())),
+        }
+    }
+}
+
+fn convert_request(request: &Request) -> Mapping {
+    let mut mapping = Mapping::new();
+    let method: String = request.method.as_deref().unwrap_or("GET").to_uppercase();
+    mapping.insert("method".into(), method.into());
+    mapping.insert("url".into(), template(&request.url).into());
+
+    let mut query: Vec<(String, String)> = enabled(&request.parameters);
+    let mut headers: Vec<(String, String)> = enabled(&request.headers);
+
+    if let Some(auth) = request.authentication.as_ref().filter(|auth| !auth.disabled) {
+        let value = |value: &Option<String>| template(value.as_deref().unwrap_or_default());
+        match auth.kind.as_deref() {
+            None | Some("none") => {}
+            Some("bearer") => {
+                let prefix: &str =
+                    auth.prefix.as_deref().filter(|p| !p.is_empty()).unwrap_or("Bearer");
+                let token: String = value(&auth.token);
+                headers.push((String::from("Authorization"), format!("{prefix} {token}")));
+            }
+            Some("basic") => {
+                let credentials: String =
+                    format!("{}:{}", value(&auth.username), value(&auth.password));
+                if credentials.contains("{{") {
+                    log::warn!(
+                        "Basic authentication with variables is not supported and was left out"
+                    );
+                } else {
+                    let credentials: String = base64::encode(credentials);
+                    headers.push((String::from("Authorization"), format!("Basic {credentials}")));
+                }
+            }
+            Some("apikey") => match auth.add_to.as_deref() {
+                Some("queryParams") => query.push((value(&auth.key), value(&auth.value))),
+                _ => headers.push((value(&auth.key), value(&auth.value))),
+            },
+            Some(kind) => {
+                log::warn!("Authentication of type {kind} is not supported and was left out")
+            }
+        }
+    }
+
+    if !query.is_empty() {
+        mapping.insert("query".into(), import::params(query));
+    }
+
+    let mut converted_body: Option<(&str, Value)> = None;
+    if let Some(body) = &request.body {
+        let mime_type: Option<&str> = body.mime_type.as_deref();
+        let converted: Option<(&str, Value)> = match mime_type {
+            Some("application/x-www-form-urlencoded") => {
+                Some(("form", import::params(enabled(&body.params))))
+            }
+            Some("multipart/form-data") => {
+                headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+                let parts: Mapping = body
+                    .params
+                    .iter()
+                    .filter(|p| !p.disabled)
+                    .map(|p| {
+                        let part: Value = match (p.kind.as_deref(), &p.file_name) {
+                            (Some("file"), Some(file)) => {
+                                let mut part = Mapping::new();
+                                part.insert("file".into(), file.as_str().into());
+                                Value::Mapping(part)
+                            }
+                            _ => template(&p.value).into(),
+                        };
+                        (p.name.as_str().into(), part)
+                    })
+                    .collect();
+                Some(("multipart", Value::Mapping(parts)))
+            }
+            _ => match (&body.text, &body.file_name) {
+                (Some(text), _) if !text.is_empty() => {
+                    Some(import::text_body(&template(text), mime_type))
+                }
+                (_, Some(file)) => Some(("body_file", file.as_str().into())),
+                _ => None,
+            },
+        };
+
+        if let Some((key, value)) = converted {
+            if let Some(mime_type) = mime_type.filter(|_| key != "multipart") {
+                if !headers.iter().any(|(k, _)| k.eq_ignore_ascii_case("content-type")) {
+                    headers.push((String::from("Content-Type"), mime_type.to_string()));
+                }
+            }
+            converted_body = Some((key, value));
+        }
+    }
+
+    if !headers.is_empty() {
+        let headers: Mapping = headers.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        mapping.insert("headers".into(), Value::Mapping(headers));
+    }
+
+    if let Some((key, value)) = converted_body {
+        mapping.insert(key.into(), value);
+    }
+
+    mapping
+}
+
+fn enabled(params: &[Param]) -> Vec<(String, String)> {
+    params
+        .iter()
+        .filter(|p| !p.disabled)
+        .map(|p| (p.name.clone(), template(&p.value)))
+        .collect()
+}
+
+/// Convert a Nunjucks template, where variables are referred to as `{{ _.name }}` or `{{ name }}`
+fn template(input: &str) -> String {
+    if input.contains("{%") {
+        log::warn!("Template tags are not supported and must be replaced: {input}");
+    }
+    import::variables(input, |name| name.trim_start_matches("_."))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::convert;
+
+    #[test]
+    fn test_convert_insomnia_export() {
+        let export = r#"{
+            "_type": "export",
+            "__export_format": 4,
+            "resources": [
+                { "_id": "wrk_1", "_type": "workspace", "name": "Pets" },
+                { "_id": "fld_1", "_type": "request_group", "parentId": "wrk_1", "name": "Pets" },
+                {
+                    "_id": "req_1", "_type": "request", "parentId": "fld_1", "name": "Create pet",
+                    "method": "POST", "url": "{{ _.base_url }}/pets",
+                    "body": { "mimeType": "application/json", "text": "{\"name\": \"{{ _.name }}\"}" },
+                    "headers": [{ "name": "Accept", "value": "application/json" }],
+                    "parameters": [{ "name": "dry_run", "value": "true", "disabled": true }],
+                    "authentication": { "type": "bearer", "token": "{{ _.token }}" }
+                },
+                { "_id": "env_1", "_type": "environment", "parentId": "wrk_1", "name": "Base Environment",
+                  "data": { "base_url": "https://example.com", "api": { "version": 2 } } },
+                { "_id": "env_2", "_type": "environment", "parentId": "env_1", "name": "Staging",
+                  "data": { "base_url": "https://staging.example.com" } },
+                { "_id": "jar_1", "_type": "cookie_jar", "parentId": "wrk_1" }
+            ]
+        }"#;
+
+        let import = convert(export).unwrap();
+        let request = &import.requests()[0].request;
+
+        assert_eq!(Path::new("pets/create_pet"), import.requests()[0].path);
+        assert_eq!(request["url"], "{{base_url}}/pets");
+        assert!(request.get("query").is_none());
+        assert_eq!(request["headers"]["Authorization"], "Bearer {{token}}");
+        assert_eq!(request["headers"]["Content-Type"], "application/json");
+        assert_eq!(request["json"]["name"], "{{name}}");
+
+        let base = &import.envs()[0];
+        assert_eq!(Path::new(".env"), base.path);
+        assert_eq!(("api.version".to_string(), "2".to_string()), base.vars[0]);
+        assert_eq!(Path::new("staging.env"), import.envs()[1].path);
+    }
+}
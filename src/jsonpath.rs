@@ -0,0 +1,141 @@
+use serde_json::Value;
+
+/// A segment of a JSONPath expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+/// Select all values in `value` matching the JSONPath `path`.
+///
+/// Only a subset of JSONPath is supported: the root `$`, child keys using dot (`$.data.id`) or
+/// bracket notation (`$['data']`), array indices (`$.items[0]`) and wildcards (`$.items[*].id`).
+/// The leading `$` may be omitted.
+pub fn select<'a>(value: &'a Value, path: &str) -> Result<Vec<&'a Value>, String> {
+    let segments: Vec<Segment> = parse(path)?;
+    let mut current: Vec<&Value> = vec![value];
+    for segment in segments {
+        current = current
+            .into_iter()
+            .flat_map(|value| match (&segment, value) {
+                (Segment::Key(key), Value::Object(map)) => map.get(key).into_iter().collect(),
+                (Segment::Index(i), Value::Array(items)) => items.get(*i).into_iter().collect(),
+                (Segment::Wildcard, Value::Object(map)) => map.values().collect(),
+                (Segment::Wildcard, Value::Array(items)) => items.iter().collect(),
+                _ => Vec::new(),
+            })
+            .collect();
+    }
+    Ok(current)
+}
+
+/// Select the first value matching `path`, formatted as a string. Strings are returned without
+/// quotes, any other value is returned as JSON.
+pub fn select_str(value: &Value, path: &str) -> Result<Option<String>, String> {
+    let selected: Vec<&Value> = select(value, path)?;
+    Ok(selected.first().map(|value| match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }))
+}
+
+fn parse(path: &str) -> Result<Vec<Segment>, String> {
+    let path: &str = path.trim();
+    let rest: String = match path.strip_prefix('$') {
+        Some(rest) => rest.to_string(),
+        None if path.starts_with('[') => path.to_string(),
+        None => format!(".{path}"),
+    };
+
+    let chars: Vec<char> = rest.chars().collect();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut i: usize = 0;
+
+    while i < chars.len() {
+        match chars[i] {
+            '.' => {
+                let start: usize = i + 1;
+                let end: usize = chars[start..]
+                    .iter()
+                    .position(|c| *c == '.' || *c == '[')
+                    .map(|p| start + p)
+                    .unwrap_or(chars.len());
+                let key: String = chars[start..end].iter().collect();
+                match key.as_str() {
+                    "" => return Err(format!("Unsupported or empty key in JSONPath {path}")),
+                    "*" => segments.push(Segment::Wildcard),
+                    _ => segments.push(Segment::Key(key)),
+                }
+                i = end;
+            }
+            '[' => {
+                let end: usize = chars[i..]
+                    .iter()
+                    .position(|c| *c == ']')
+                    .map(|p| i + p)
+                    .ok_or_else(|| format!("Missing closing bracket in JSONPath {path}"))?;
+                let inner: String = chars[i + 1..end].iter().collect();
+                let inner: &str = inner.trim();
+                let quoted: Option<(char, &str)> = ['\'', '"']
+                    .into_iter()
+                    .find_map(|quote| inner.strip_prefix(quote).map(|rest| (quote, rest)));
+                if inner == "*" {
+                    segments.push(Segment::Wildcard);
+                } else if let Some((quote, rest)) = quoted {
+                    let key: &str = rest
+                        .strip_suffix(quote)
+                        .ok_or_else(|| format!("Missing closing quote in JSONPath {path}"))?;
+                    segments.push(Segment::Key(key.to_string()));
+                } else {
+                    let index: usize = inner
+                        .parse()
+                        .map_err(|_| format!("Invalid index {inner} in JSONPath {path}"))?;
+                    segments.push(Segment::Index(index));
+                }
+                i = end + 1;
+            }
+            c => return Err(format!("Unexpected character {c} in JSONPath {path}")),
+        }
+    }
+
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{select, select_str};
+
+    #[test]
+    fn test_select_json_path() {
+        let value = json!({
+            "data": {
+                "token": "abc",
+                "items": [{ "id": 1 }, { "id": 2 }],
+                "odd key": true
+            }
+        });
+
+        assert_eq!(Some("abc".to_string()), select_str(&value, "$.data.token").unwrap());
+        assert_eq!(Some("abc".to_string()), select_str(&value, "data.token").unwrap());
+        assert_eq!(Some("2".to_string()), select_str(&value, "$.data.items[1].id").unwrap());
+        assert_eq!(Some("true".to_string()), select_str(&value, "$.data['odd key']").unwrap());
+        assert_eq!(2, select(&value, "$.data.items[*].id").unwrap().len());
+        assert_eq!(None, select_str(&value, "$.data.missing").unwrap());
+        assert!(select(&value, "$..token").is_err());
+    }
+
+    #[test]
+    fn test_reject_malformed_quoted_keys() {
+        let value = json!({ "é": 1 });
+
+        assert_eq!(Some("1".to_string()), select_str(&value, "$['é']").unwrap());
+        assert_eq!(Some("1".to_string()), select_str(&value, "$[\"é\"]").unwrap());
+        assert!(select(&value, "$['é]").is_err());
+        assert!(select(&value, "$[\"é']").is_err());
+        assert!(select(&value, "$[']").is_err());
+    }
+}
@@ -3,6 +3,8 @@ use log::{Level, LevelFilter, Record};
 use std::io;
 use std::io::Write;
 
+use crate::redact::redact;
+
 pub fn setup_logging(verbosity_level: u8) {
     match std::env::var("RUST_LOG") {
         Ok(_) => log_by_env_var(),
@@ -28,19 +30,22 @@ fn log_by_cmd_arg(verbosity_level: u8) {
     env_logger::builder().format(formatter).filter_level(filter).init()
 }
 
+/// Format a log record, with any secret values masked
 fn formatter(buf: &mut Formatter, record: &Record) -> io::Result<()> {
+    let message = record.args().to_string();
+    let message = redact(&message);
     match record.level() {
-        Level::Info => writeln!(buf, "{}", record.args()),
+        Level::Info => writeln!(buf, "{}", message),
         Level::Warn => {
             let mut style = buf.style();
             style.set_color(Color::Yellow);
-            writeln!(buf, "{}: {}", style.value(record.level()), record.args())
+            writeln!(buf, "{}: {}", style.value(record.level()), message)
         }
         Level::Error => {
             let mut style = buf.style();
             style.set_color(Color::Red);
-            writeln!(buf, "{}: {}", style.value(record.level()), record.args())
+            writeln!(buf, "{}: {}", style.value(record.level()), message)
         }
-        _ => writeln!(buf, "{}: {}", record.level(), record.args()),
+        _ => writeln!(buf, "{}: {}", record.level(), message),
     }
 }
@@ -1,34 +1,69 @@
+mod age;
 mod args;
+mod body;
+mod capture;
+mod curl;
 mod dbg;
+mod document;
+mod dotenv;
 mod error;
+mod expect;
 mod format;
+mod har;
 mod headers;
+mod helpers;
 mod http;
+mod httpfile;
+mod import;
+mod insomnia;
 mod io;
+mod jsonpath;
 mod logger;
+mod openapi;
+mod params;
+mod postman;
+mod prompt;
 mod prop;
+mod redact;
+mod resolve;
+mod runner;
+mod schema;
+mod secrets;
 mod template;
 
-use crate::args::Args;
+use crate::args::{Args, Command, Import, Keys, Secrets};
+use crate::body::BodyError;
+use crate::capture::{CaptureError, State};
+use crate::curl::{CurlCommand, CurlError};
 use crate::dbg::dbg_info;
+use crate::document::{DocumentError, Entry, RequestFile};
 use crate::error::exit;
+use crate::expect::Outcome;
 use crate::format::ContentFormatter;
+use crate::har::Har;
 use crate::http::HttpRequest;
+use crate::import::ImportError;
 use crate::io::write;
 use crate::io::write_color;
 use crate::io::writeln;
+use crate::io::writeln_color;
 use crate::io::writeln_spec;
 use crate::logger::setup_logging;
-use crate::prop::Property;
+use crate::openapi::Spec;
+use crate::prop::{ParsePropertyError, Property, Source};
+use crate::runner::{CaseResult, Summary, TestCase};
+use crate::secrets::SecretsError;
 use crate::template::substitution;
 use clap::Parser;
 use error::FireError;
 use reqwest::blocking::Response;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
-use std::str::FromStr;
 use std::time::Duration;
 use std::time::Instant;
-use template::SubstitutionError;
+use std::time::SystemTime;
+use template::{Options, SubstitutionError};
 use termcolor::{Color, ColorSpec, StandardStream};
 
 fn main() -> ExitCode {
@@ -40,6 +75,7 @@ fn main() -> ExitCode {
 
 fn exec() -> Result<(), FireError> {
     let args: Args = Args::parse();
+    redact::set_enabled(!args.show_secrets());
     setup_logging(args.verbosity_level);
     log::debug!("Config: {:?}", args);
 
@@ -50,31 +86,135 @@ fn exec() -> Result<(), FireError> {
         return Ok(());
     }
 
+    if let Some(command) = args.command() {
+        return run_command(&args, command, &mut stdout);
+    }
+
+    if args.is_collection() {
+        return run_collection(&args, &mut stdout);
+    }
+
     // 1. Read file content
-    let file = match std::fs::read_to_string(args.file()) {
-        Ok(file) => file,
-        Err(e) => {
-            return match e.kind() {
-                std::io::ErrorKind::NotFound => {
-                    Err(FireError::FileNotFound(args.file().to_path_buf()))
-                }
-                std::io::ErrorKind::PermissionDenied => {
-                    Err(FireError::NoReadPermission(args.file().to_path_buf()))
-                }
-                _ => Err(FireError::GenericIO(e.to_string())),
-            }
-        }
-    };
+    let file: String =
+        std::fs::read_to_string(args.file()).map_err(|e| FireError::io(e, args.file()))?;
+    let requests = RequestFile::parse_file(args.file(), &file);
+
+    if args.list() {
+        list(&mut stdout, &requests, None);
+        return Ok(());
+    }
+
+    let entry: Entry = requests.select(args.name())?;
+
     // 2. Read enviroment variables from system environment and extra environments supplied via cli
     // 3. Apply template substitution
-    let props: Vec<Property> = args.env().expect("Unable to load env vars");
+    let props: Vec<Property> = args.env()?;
 
     log::debug!("Received properties {:?}", props);
 
-    let content: String = substitution(file, props)?;
+    let options: Options = Options::default()
+        .with_external(args.allow_exec())
+        .with_partials(&args.partial_dirs())?;
+    let mut execution: Execution =
+        execute(&args, args.file(), &entry, props, &options, &mut stdout)?;
+    if let Some(path) = args.har() {
+        let har = Har::new(execution.har.take().into_iter().collect());
+        write_output(&mut stdout, Some(path), &redact::redact(&har.to_json()))?;
+    }
+
+    let failed: usize = execution.failed();
+    let total: usize = execution.outcomes.len();
+    let contract: bool = execution.outcomes.iter().any(|o| !o.passed() && o.is_contract());
+    match failed {
+        0 => {}
+        failed if contract => return Err(FireError::Contract(failed, total)),
+        failed => return Err(FireError::Expectation(failed, total)),
+    }
+
+    Ok(())
+}
+
+/// Render a template with `props`. If any variables are missing values and prompting is enabled,
+/// the user is prompted for their values, which are added to `props`, and the template is rendered
+/// again.
+fn prompted<T>(
+    args: &Args,
+    props: &mut Vec<Property>,
+    render: impl Fn(Vec<Property>) -> Result<T, DocumentError>,
+) -> Result<T, FireError> {
+    match render(props.clone()) {
+        Err(DocumentError::Substitution(SubstitutionError::Unresolved(names))) if args.prompt() => {
+            let values: Vec<Property> = prompt::values(&names, &args.secret_names())
+                .map_err(|e| FireError::GenericIO(e.to_string()))?;
+            props.extend(values);
+            Ok(render(props.clone())?)
+        }
+        result => Ok(result?),
+    }
+}
+
+/// The result of a request that was executed and received a response
+struct Execution {
+    duration: Duration,
+    outcomes: Vec<Outcome>,
+    har: Option<har::Entry>,
+}
+
+impl Execution {
+    fn failed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| !outcome.passed()).count()
+    }
+}
+
+fn execute(
+    args: &Args,
+    path: &Path,
+    entry: &Entry,
+    mut props: Vec<Property>,
+    options: &Options,
+    stdout: &mut StandardStream,
+) -> Result<Execution, FireError> {
+    match State::load(&args.project_root()) {
+        Ok(state) => props.extend(state.properties()),
+        Err(e) => log::warn!("Unable to read captured values: {:?}", e),
+    }
 
     // 4. Parse Validate format of request
-    let request: HttpRequest = HttpRequest::from_str(&content).unwrap();
+    let mut request: HttpRequest =
+        prompted(args, &mut props, |props| entry.render(props, options))?;
+
+    let base_dir: &Path = path.parent().unwrap_or_else(|| Path::new(""));
+    if let Some(body_file) = request.body_file().cloned() {
+        let content: Vec<u8> = body_file.read(base_dir)?;
+        let content: Vec<u8> = if body_file.template() {
+            let content: String = String::from_utf8(content)
+                .map_err(|_| BodyError::Encoding(body_file.path().to_path_buf()))?;
+            prompted(args, &mut props, |props| Ok(substitution(content.clone(), props, options)?))?
+                .into_bytes()
+        } else {
+            content
+        };
+        request.set_content(content);
+    }
+
+    let names: Vec<String> = entry.variables(options)?;
+    register_secrets(&props, &names, &request);
+
+    if args.curl() {
+        let secrets: Vec<String> = match args.redact() {
+            true => redact::secrets(),
+            false => Vec::new(),
+        };
+        let secrets: Vec<&str> = secrets.iter().map(String::as_str).collect();
+        let curl: String = curl::to_curl(&request, base_dir, &secrets);
+        write_output(stdout, None, &format!("{curl}\n"))?;
+        return Ok(Execution {
+            duration: Duration::ZERO,
+            outcomes: Vec::new(),
+            har: None,
+        });
+    }
+
     // 5. Add user-agent header if missing
     // 6. Add content-length header if missing
     // 7. Make (and optionally print) request
@@ -90,30 +230,41 @@ fn exec() -> Result<(), FireError> {
 
     if args.print_request() {
         let title: String = format!("{} {}", request.verb(), request.url().unwrap());
-        writeln(&mut stdout, &title);
+        writeln(stdout, &title);
         let border = "━".repeat(title.len());
-        writeln(&mut stdout, &border);
+        writeln(stdout, &border);
 
         if args.headers {
             let mut spec = ColorSpec::new();
             spec.set_dimmed(true);
             for (k, v) in &req_headers {
-                writeln_spec(&mut stdout, &format!("{}: {:?}", k.as_str(), v), &spec);
+                let value = String::from_utf8_lossy(v.as_bytes());
+                let value = redact::header(k.as_str(), &value);
+                writeln_spec(stdout, &format!("{}: {:?}", k.as_str(), value), &spec);
             }
-            if request.body().is_some() {
-                writeln(&mut stdout, "");
+            if request.body().is_some() || request.multipart().is_some() {
+                writeln(stdout, "");
             }
         }
 
-        if let Some(body) = request.body() {
-            let content: String = formatters
-                .iter()
-                .filter(|fmt| fmt.accept(content_type))
-                .fold(body.clone(), |content, fmt| fmt.format(content).unwrap());
+        if let Some(multipart) = request.multipart() {
+            for (name, part) in multipart.iter() {
+                writeln(stdout, &format!("{name}: {part}"));
+            }
+        }
 
-            writeln(&mut stdout, &content);
+        if let Some(body) = request.body() {
+            let content: String = match String::from_utf8(body) {
+                Ok(body) => formatters
+                    .iter()
+                    .filter(|fmt| fmt.accept(content_type))
+                    .fold(body, |content, fmt| fmt.format(content).unwrap()),
+                Err(e) => format!("<{} bytes of binary data>", e.as_bytes().len()),
+            };
+
+            writeln(stdout, &content);
         }
-        writeln(&mut stdout, "");
+        writeln(stdout, "");
     }
 
     let req = client
@@ -121,11 +272,14 @@ fn exec() -> Result<(), FireError> {
         .timeout(args.timeout())
         .headers(req_headers);
 
-    let req = match request.body() {
-        Some(body) => req.body(body.clone()).build().unwrap(),
-        None => req.build().unwrap(),
+    let req = match (request.multipart(), request.body()) {
+        (Some(multipart), _) => req.multipart(multipart.form(base_dir)?).build().unwrap(),
+        (None, Some(body)) => req.body(body).build().unwrap(),
+        (None, None) => req.build().unwrap(),
     };
 
+    let har_request: Option<har::Request> = args.har().map(|_| har::Request::from(&req));
+    let started: SystemTime = SystemTime::now();
     let start: Instant = Instant::now();
     let resp: Result<Response, reqwest::Error> = client.execute(req);
     let end: Instant = Instant::now();
@@ -146,7 +300,7 @@ fn exec() -> Result<(), FireError> {
     // 8. Print response if successful, or error, if not
 
     let version = resp.version();
-    let status = resp.status();
+    let status_code = resp.status();
     let headers = resp.headers().clone();
     let body = match resp.text() {
         Ok(body) => body,
@@ -155,7 +309,12 @@ fn exec() -> Result<(), FireError> {
 
     log::debug!("Body of response:\n{body}");
 
-    let status_color: Option<Color> = match status.as_u16() {
+    let har: Option<har::Entry> = har_request.map(|har_request| {
+        let response = har::Response::new(version, status_code, &headers, &body);
+        har::Entry::new(started, duration, har_request, response)
+    });
+
+    let status_color: Option<Color> = match status_code.as_u16() {
         200..=299 => Some(Color::Green),
         400..=499 => Some(Color::Yellow),
         500..=599 => Some(Color::Red),
@@ -169,29 +328,33 @@ fn exec() -> Result<(), FireError> {
     };
 
     let version: String = format!("{version:?} ");
-    write(&mut stdout, &version);
+    write(stdout, &version);
 
-    let status: String = status.to_string();
-    write_color(&mut stdout, &status, status_color);
+    let status: String = status_code.to_string();
+    write_color(stdout, &status, status_color);
 
     let outcome: String = format!(" {} ms {} {}", duration.as_millis(), body_len, unit);
-    writeln(&mut stdout, &outcome);
+    writeln(stdout, &outcome);
 
     let border_len: usize = version.len() + status.len() + outcome.len();
     let border = "━".repeat(border_len);
-    writeln(&mut stdout, &border);
+    writeln(stdout, &border);
 
     if args.headers {
         let mut spec = ColorSpec::new();
         spec.set_dimmed(true);
         for (k, v) in headers.clone() {
             match k {
-                Some(k) => writeln_spec(&mut stdout, &format!("{}: {:?}", k, v), &spec),
+                Some(k) => {
+                    let value = String::from_utf8_lossy(v.as_bytes());
+                    let value = redact::header(k.as_str(), &value);
+                    writeln_spec(stdout, &format!("{}: {:?}", k, value), &spec)
+                }
                 None => log::warn!("Found header key that was empty or unresolvable"),
             }
         }
         if !body.is_empty() {
-            io::writeln(&mut stdout, "");
+            io::writeln(stdout, "");
         }
     }
 
@@ -200,21 +363,495 @@ fn exec() -> Result<(), FireError> {
         let content: String = formatters
             .iter()
             .filter(|fmt| fmt.accept(content_type))
-            .fold(body, |content, fmt| fmt.format(content).unwrap());
+            .fold(body.clone(), |content, fmt| fmt.format(content).unwrap());
 
-        io::write(&mut stdout, &content);
+        io::write(stdout, &content);
         if !content.ends_with('\n') {
-            io::writeln(&mut stdout, "");
+            io::writeln(stdout, "");
+        }
+    }
+
+    // 9. Capture values from response for later requests
+    if !request.captures().is_empty() {
+        let mut state = State::load(&args.project_root())?;
+        for (name, capture) in request.captures() {
+            match capture.extract(&headers, &body)? {
+                Some(value) => {
+                    if capture.is_secret(name) {
+                        redact::add(&value);
+                    }
+                    log::info!("Captured value {name}");
+                    state.insert(name.clone(), value);
+                }
+                None => log::warn!("No value found to capture for {name}"),
+            }
+        }
+        state.save()?;
+    }
+
+    // 10. Verify expectations on response
+    let expectations = request.expectations();
+    let mut outcomes: Vec<Outcome> =
+        expectations.evaluate(status_code, &headers, &body, duration, base_dir);
+
+    let spec: Option<PathBuf> = match (args.openapi(), request.openapi()) {
+        (Some(path), _) => Some(path.to_path_buf()),
+        (None, Some(path)) => Some(base_dir.join(path)),
+        (None, None) => None,
+    };
+    if let Some(path) = spec {
+        let spec: Spec = Spec::parse(&read_file(&path)?)
+            .map_err(|e| FireError::Other(format!("Unable to use {:?}. {e}", path)))?;
+        let url = request.url().unwrap();
+        match spec.find(&request.verb().to_string(), &url) {
+            Some(op) => outcomes.extend(spec.conformance(&op, status_code, &headers, &body)),
+            None => outcomes.push(Outcome::new(
+                format!("{} {} is an operation in {:?}", request.verb(), url.path(), path),
+                Some(String::from("no matching operation")),
+            )),
+        }
+    }
+    if !outcomes.is_empty() {
+        writeln(stdout, "");
+        for outcome in &outcomes {
+            match outcome.failure() {
+                None => {
+                    write_color(stdout, "✓ ", Some(Color::Green));
+                    writeln(stdout, outcome.description());
+                }
+                Some(failure) => {
+                    write_color(stdout, "✗ ", Some(Color::Red));
+                    write(stdout, outcome.description());
+                    writeln_color(stdout, &format!(" ({failure})"), Some(Color::Red));
+                }
+            }
+        }
+    }
+
+    Ok(Execution {
+        duration,
+        outcomes,
+        har,
+    })
+}
+
+/// Mark the values of sensitive headers of `request`, and the values of secret properties that are
+/// used in it, as secrets that are masked in all output. A secret is used when the template refers
+/// to it by one of `names`, which also covers expectations and values that only appear encoded in the
+/// request, or when its value is found in the request itself, such as through a body template file.
+fn register_secrets(props: &[Property], names: &[String], request: &HttpRequest) {
+    let mut used: Vec<String> = request.url().map(|url| url.to_string()).into_iter().collect();
+    for (name, value) in &request.headers() {
+        let value: String = String::from_utf8_lossy(value.as_bytes()).to_string();
+        if redact::is_sensitive_header(name.as_str()) {
+            redact::add(&value);
+        }
+        used.push(value);
+    }
+    used.extend(request.body().map(|body| String::from_utf8_lossy(&body).to_string()));
+    if let Some(multipart) = request.multipart() {
+        used.extend(multipart.iter().filter_map(|(_, part)| part.value().map(String::from)));
+    }
+
+    props
+        .iter()
+        .filter(|prop| prop.is_secret())
+        .filter(|prop| {
+            names.iter().any(|name| name == prop.key())
+                || used.iter().any(|text| text.contains(prop.value()))
+        })
+        .for_each(|prop| redact::add(prop.value()));
+}
+
+/// Print each variable used by the selected request, where its value comes from and the definitions
+/// it overrides, followed by warnings for variables that are defined but never used
+fn show_env(args: &Args, stdout: &mut StandardStream) -> Result<(), FireError> {
+    let requests = RequestFile::parse_file(args.file(), &read_file(args.file())?);
+    let entry: Entry = requests.select(args.name())?;
+    let options: Options = Options::default()
+        .with_external(args.allow_exec())
+        .with_partials(&args.partial_dirs())?;
+    let names: Vec<String> = entry.variables(&options)?;
+
+    let mut props: Vec<Property> = args.env()?;
+    match State::load(&args.project_root()) {
+        Ok(state) => props.extend(state.properties()),
+        Err(e) => log::warn!("Unable to read captured values: {:?}", e),
+    }
+
+    let value = |prop: &Property| -> String {
+        match prop.is_secret() {
+            true => String::from("***"),
+            false => prop.value().escape_debug().to_string(),
+        }
+    };
+    let origin = |prop: &Property| -> String {
+        match (prop.source(), prop.origin()) {
+            (Source::EnvVar, _) => String::from("environment variable"),
+            (Source::Arg, _) => String::from("--variable"),
+            (_, Some(path)) => path.display().to_string(),
+            (_, None) => String::from("request file"),
+        }
+    };
+
+    let mut dimmed = ColorSpec::new();
+    dimmed.set_dimmed(true);
+    let mut warning = ColorSpec::new();
+    warning.set_fg(Some(Color::Yellow));
+
+    let variables: Vec<resolve::Variable> = resolve::resolve(&names, &props);
+    let width: usize = names.iter().map(String::len).max().unwrap_or(0);
+    let values = variables.iter().flat_map(|v| v.value().into_iter().chain(v.shadowed()));
+    let value_width: usize = values.map(|prop| value(prop).chars().count()).max().unwrap_or(0);
+    for variable in &variables {
+        write(stdout, &format!("{:width$}  ", variable.name()));
+        match variable.value() {
+            Some(prop) => {
+                write(stdout, &format!("{:value_width$}  ", value(prop)));
+                writeln_spec(stdout, &origin(prop), &dimmed);
+            }
+            None => writeln_spec(stdout, "not defined", &warning),
+        }
+        for prop in variable.shadowed() {
+            let line: String = format!(
+                "{:width$}  {:value_width$}  {} (overridden)",
+                "",
+                value(prop),
+                origin(prop)
+            );
+            writeln_spec(stdout, &line, &dimmed);
+        }
+    }
+
+    for prop in resolve::unused(&names, &props) {
+        let msg: String =
+            format!("warning: {} is defined in {} but never used", prop.key(), origin(prop));
+        writeln_spec(stdout, &msg, &warning);
+    }
+
+    Ok(())
+}
+
+fn list(stdout: &mut StandardStream, requests: &RequestFile, path: Option<&Path>) {
+    let width: usize = requests.entries().iter().map(|e| e.label().len()).max().unwrap_or(0);
+    let mut spec = ColorSpec::new();
+    spec.set_dimmed(true);
+    for entry in requests.entries() {
+        if let Some(path) = path {
+            write(stdout, &format!("{}  ", path.display()));
+        }
+        write(stdout, &format!("{:width$}  ", entry.label()));
+        writeln_spec(stdout, entry.comment().unwrap_or_default(), &spec);
+    }
+}
+
+/// Execute every request in every file found in a directory or matching a glob pattern,
+/// continuing after any failure, and report the result of each request at the end
+fn run_collection(args: &Args, stdout: &mut StandardStream) -> Result<(), FireError> {
+    let files: Vec<PathBuf> = runner::request_files(args.file())?;
+    let props: Vec<Property> = args.env()?;
+    log::debug!("Received properties {:?}", props);
+    let options: Options = Options::default()
+        .with_external(args.allow_exec())
+        .with_partials(&args.partial_dirs())?;
+
+    let mut title = ColorSpec::new();
+    title.set_bold(true);
+    let mut cases: Vec<TestCase> = Vec::new();
+    let mut entries: Vec<har::Entry> = Vec::new();
+
+    for path in files {
+        let requests: RequestFile = match std::fs::read_to_string(&path) {
+            Ok(file) => RequestFile::parse_file(&path, &file),
+            Err(e) => {
+                let err: FireError = FireError::io(e, &path);
+                eprintln!("{}", redact::redact(&err.to_string()));
+                cases.push(TestCase::new(path, String::new(), Duration::ZERO, err.into()));
+                continue;
+            }
+        };
+
+        if args.list() {
+            list(stdout, &requests, Some(&path));
+            continue;
+        }
+
+        let selected = requests
+            .entries()
+            .iter()
+            .filter(|entry| args.name().map_or(true, |name| name == entry.label()));
+
+        for entry in selected {
+            writeln_spec(stdout, &format!("▶ {} › {}", path.display(), entry.label()), &title);
+            let (duration, result): (Duration, CaseResult) =
+                match execute(args, &path, entry, props.clone(), &options, stdout) {
+                    Ok(execution) => {
+                        entries.extend(execution.har);
+                        (execution.duration, execution.outcomes.into())
+                    }
+                    Err(err) => {
+                        eprintln!("{}", redact::redact(&err.to_string()));
+                        (Duration::ZERO, err.into())
+                    }
+                };
+            cases.push(TestCase::new(path.clone(), entry.label(), duration, result));
+            writeln(stdout, "");
+        }
+    }
+
+    if args.list() {
+        return Ok(());
+    }
+
+    let summary: Summary = Summary::of(&cases);
+    write_color(stdout, &format!("{} passed", summary.passed), Some(Color::Green));
+    write(stdout, ", ");
+    let color: Option<Color> = if summary.unsuccessful() > 0 { Some(Color::Red) } else { None };
+    write_color(stdout, &format!("{} failed", summary.failed), color);
+    write(stdout, ", ");
+    write_color(stdout, &format!("{} errors", summary.errors), color);
+    writeln(stdout, &format!(" ({} requests)", summary.total));
+
+    if let Some(junit) = args.junit() {
+        runner::write_report(junit, &runner::junit(&cases))?;
+    }
+    if let Some(tap) = args.tap() {
+        runner::write_report(tap, &runner::tap(&cases))?;
+    }
+    if let Some(path) = args.har() {
+        let har: String = Har::new(entries).to_json();
+        write_output(stdout, Some(path), &redact::redact(&har))?;
+    }
+
+    match summary.unsuccessful() {
+        0 => Ok(()),
+        unsuccessful => Err(FireError::Failures(unsuccessful, summary.total)),
+    }
+}
+
+fn run_command(
+    args: &Args,
+    command: &Command,
+    stdout: &mut StandardStream,
+) -> Result<(), FireError> {
+    match command {
+        Command::Env { .. } => show_env(args, stdout),
+        Command::Secrets(secrets) => run_secrets(secrets, stdout),
+        Command::Import(Import::Curl { output, command }) => {
+            let curl: CurlCommand = match command.as_slice() {
+                [] => CurlCommand::parse(&read_stdin()?)?,
+                [command] => CurlCommand::parse(command)?,
+                args => CurlCommand::from_args(args.to_vec())?,
+            };
+            write_output(stdout, output.as_deref(), &curl.to_yaml()?)
+        }
+        Command::Import(Import::Postman {
+            output,
+            environment,
+            force,
+            collection,
+        }) => {
+            let environments: Vec<String> =
+                environment.iter().map(|path| read_file(path)).collect::<Result<_, _>>()?;
+            let import = postman::convert(&read_file(collection)?, &environments)?;
+            write_import(stdout, &import, output, *force)
+        }
+        Command::Import(Import::Insomnia {
+            output,
+            force,
+            export,
+        }) => {
+            let import = insomnia::convert(&read_file(export)?)?;
+            write_import(stdout, &import, output, *force)
+        }
+        Command::Import(Import::Har {
+            output,
+            filter,
+            force,
+            har,
+        }) => {
+            let import = har::convert(&read_file(har)?, filter.as_deref())?;
+            write_import(stdout, &import, output, *force)
+        }
+        Command::Import(Import::OpenApi {
+            output,
+            force,
+            spec,
+        }) => {
+            let import = openapi::convert(&read_file(spec)?)?;
+            write_import(stdout, &import, output, *force)
         }
     }
+}
 
+fn run_secrets(command: &Secrets, stdout: &mut StandardStream) -> Result<(), FireError> {
+    let recipients = |file: &Path, keys: &Keys| {
+        let identity: PathBuf = secrets::identity_path(keys.identity.as_deref());
+        secrets::recipients(file, &keys.recipient, &keys.recipients_file, &identity)
+    };
+    match command {
+        Secrets::Encrypt { keys, force, file } => {
+            let output: PathBuf = secrets::encrypt(file, &recipients(file, keys)?, *force)?;
+            writeln(stdout, &format!("Encrypted {:?} to {:?}", file, output));
+        }
+        Secrets::Edit { keys, file } => {
+            let identity: PathBuf = secrets::identity_path(keys.identity.as_deref());
+            match secrets::edit(file, &identity, &recipients(file, keys)?)? {
+                true => writeln(stdout, &format!("Encrypted changes to {:?}", file)),
+                false => writeln(stdout, "No changes"),
+            }
+        }
+        Secrets::Keygen { output, force } => {
+            let identity: PathBuf = secrets::identity_path(output.as_deref());
+            let recipient = secrets::keygen(&identity, *force)?;
+            eprintln!("Created identity in {:?}, its public key is", identity);
+            writeln(stdout, &recipient.to_string());
+        }
+    }
+    Ok(())
+}
+
+fn read_file(path: &Path) -> Result<String, FireError> {
+    std::fs::read_to_string(path).map_err(|e| FireError::io(e, path))
+}
+
+fn write_import(
+    stdout: &mut StandardStream,
+    import: &import::Import,
+    dir: &Path,
+    force: bool,
+) -> Result<(), FireError> {
+    for path in import.write(dir, force)? {
+        writeln(stdout, &path.display().to_string());
+    }
+    writeln(
+        stdout,
+        &format!(
+            "Imported {} requests and {} environment files",
+            import.requests().len(),
+            import.envs().len()
+        ),
+    );
     Ok(())
 }
 
+fn read_stdin() -> Result<String, FireError> {
+    let mut input = String::new();
+    std::io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| FireError::GenericIO(e.to_string()))?;
+    Ok(input)
+}
+
+/// Write content to a file, or to stdout if no file is given
+fn write_output(
+    stdout: &mut StandardStream,
+    output: Option<&Path>,
+    content: &str,
+) -> Result<(), FireError> {
+    match output {
+        Some(path) => std::fs::write(path, content).map_err(|e| FireError::io(e, path)),
+        None => stdout
+            .write_all(content.as_bytes())
+            .map_err(|e| FireError::GenericIO(e.to_string())),
+    }
+}
+
 impl From<SubstitutionError> for FireError {
     fn from(e: SubstitutionError) -> Self {
         match e {
             SubstitutionError::MissingValue(err) => FireError::Template(err),
+            SubstitutionError::Unresolved(names) => {
+                FireError::Template(format!("Missing values for variables: {}", names.join(", ")))
+            }
+            SubstitutionError::InvalidSection(err) => FireError::Template(err),
+            SubstitutionError::External(err) => FireError::External(err),
+            SubstitutionError::InvalidPartial(err) => {
+                FireError::Template(format!("Invalid partial {err}"))
+            }
+        }
+    }
+}
+
+impl From<ParsePropertyError> for FireError {
+    fn from(e: ParsePropertyError) -> Self {
+        FireError::Environment(e.to_string())
+    }
+}
+
+impl From<SecretsError> for FireError {
+    fn from(e: SecretsError) -> Self {
+        match e {
+            SecretsError::File(path, err) => FireError::io(err, &path),
+            err => FireError::Environment(err.to_string()),
+        }
+    }
+}
+
+impl From<BodyError> for FireError {
+    fn from(e: BodyError) -> Self {
+        match e {
+            BodyError::File(path, err) => FireError::io(err, &path),
+            BodyError::ContentType(ct) => FireError::Other(format!("Invalid content type {ct}")),
+            BodyError::Encoding(path) => {
+                FireError::Template(format!("Body file {:?} is not valid UTF-8", path))
+            }
+        }
+    }
+}
+
+impl From<DocumentError> for FireError {
+    fn from(e: DocumentError) -> Self {
+        match e {
+            DocumentError::Substitution(e) => e.into(),
+            DocumentError::Parse(err) => FireError::InvalidRequest(err),
+            DocumentError::NotFound(name, names) => FireError::RequestNotFound(format!(
+                "No request named '{name}', available requests are: {}",
+                names.join(", ")
+            )),
+            DocumentError::Ambiguous(names) => FireError::RequestNotFound(format!(
+                "File contains several requests, select one with --name: {}",
+                names.join(", ")
+            )),
+            DocumentError::Empty => FireError::InvalidRequest(String::from("No request found")),
+        }
+    }
+}
+
+impl From<CaptureError> for FireError {
+    fn from(e: CaptureError) -> Self {
+        match e {
+            CaptureError::JsonPath(err) => FireError::InvalidRequest(err),
+            CaptureError::Regex(err) => FireError::InvalidRequest(err),
+            CaptureError::State(path, err) => {
+                FireError::GenericIO(format!("Unable to use captured values in {:?}: {err}", path))
+            }
+        }
+    }
+}
+
+impl From<CurlError> for FireError {
+    fn from(e: CurlError) -> Self {
+        match e {
+            CurlError::Syntax(err) => FireError::Import(format!("Invalid curl command: {err}")),
+            CurlError::MissingValue(opt) => {
+                FireError::Import(format!("Missing value for curl option {opt}"))
+            }
+            CurlError::MissingUrl => FireError::Import(String::from("No URL in curl command")),
+            CurlError::Unsupported(err) => FireError::Import(format!("Not supported: {err}")),
+        }
+    }
+}
+
+impl From<ImportError> for FireError {
+    fn from(e: ImportError) -> Self {
+        match e {
+            ImportError::File(path, err) => FireError::io(err, &path),
+            ImportError::Exists(path) => FireError::Import(format!(
+                "File {:?} already exists, use --force to overwrite it",
+                path
+            )),
+            ImportError::Parse(err) => FireError::Import(err),
         }
     }
 }
@@ -0,0 +1,753 @@
+use std::fmt::Display;
+use std::path::PathBuf;
+
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use reqwest::header::HeaderMap;
+use reqwest::{StatusCode, Url};
+use serde_yaml::{Mapping, Value};
+
+use crate::expect::Outcome;
+use crate::import::{self, Import, ImportError};
+use crate::schema::Validator;
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+/// Header parameters that are not allowed to be described as parameters in OpenAPI
+const IGNORED_HEADERS: [&str; 3] = ["accept", "content-type", "authorization"];
+const BASE_URL: &str = "base_url";
+/// How many references are followed, when resolving a reference or generating an example
+const MAX_DEPTH: usize = 8;
+
+lazy_static! {
+    static ref PATH_PARAM: Regex = Regex::new(r"\{([^{}]+)\}").unwrap();
+}
+
+/// An OpenAPI 3 document, in YAML or JSON
+#[derive(Debug)]
+pub struct Spec {
+    root: Value,
+    json: serde_json::Value,
+}
+
+/// An operation in an OpenAPI document, such as `GET /pets/{petId}`
+#[derive(Debug)]
+pub struct Operation<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    item: &'a Value,
+    operation: &'a Value,
+}
+
+impl Spec {
+    pub fn parse(input: &str) -> Result<Spec, String> {
+        let root: Value =
+            serde_yaml::from_str(input).map_err(|e| format!("Invalid OpenAPI document: {e}"))?;
+        let json: serde_json::Value =
+            serde_json::to_value(&root).map_err(|e| format!("Invalid OpenAPI document: {e}"))?;
+        match root.get("openapi").and_then(scalar) {
+            Some(v) if v.starts_with("3.") => Ok(Spec { root, json }),
+            Some(v) => Err(format!("OpenAPI version {v} is not supported, only version 3 is")),
+            None => Err(String::from("Not an OpenAPI document, no `openapi` version found")),
+        }
+    }
+
+    /// The URL of the first server
+    pub fn server(&self) -> Option<String> {
+        self.servers().into_iter().next()
+    }
+
+    /// The URLs of all servers, with the default value of any server variables
+    fn servers(&self) -> Vec<String> {
+        let servers = self.root.get("servers").and_then(Value::as_sequence).into_iter().flatten();
+        servers
+            .filter_map(|server| {
+                let url: &str = server.get("url")?.as_str()?;
+                let url: String = PATH_PARAM
+                    .replace_all(url, |caps: &Captures| {
+                        server
+                            .get("variables")
+                            .and_then(|vars| vars.get(&caps[1]))
+                            .and_then(|var| var.get("default"))
+                            .and_then(scalar)
+                            .unwrap_or_else(|| caps[0].to_string())
+                    })
+                    .to_string();
+                Some(url.trim_end_matches('/').to_string())
+            })
+            .collect()
+    }
+
+    /// The operation matching the method and the path of a URL, where the path of a server may
+    /// precede the path of the operation. A path without parameters is preferred over a path with
+    /// parameters, so `/pets/mine` is preferred over `/pets/{petId}`.
+    pub fn find(&self, method: &str, url: &Url) -> Option<Operation<'_>> {
+        let bases: Vec<String> = self
+            .servers()
+            .iter()
+            .map(|server| match Url::parse(server) {
+                Ok(server) => server.path().trim_end_matches('/').to_string(),
+                Err(_) => server.clone(),
+            })
+            .chain([String::new()])
+            .collect();
+        let paths: Vec<&str> =
+            bases.iter().filter_map(|base| url.path().strip_prefix(base.as_str())).collect();
+
+        self.operations()
+            .into_iter()
+            .filter(|op| op.method.eq_ignore_ascii_case(method))
+            .filter(|op| {
+                let pattern: String = PATH_PARAM
+                    .split(op.path)
+                    .map(regex::escape)
+                    .collect::<Vec<String>>()
+                    .join("[^/]+");
+                let pattern = Regex::new(&format!("^{pattern}/?$")).unwrap();
+                paths.iter().any(|path| pattern.is_match(path))
+            })
+            .min_by_key(|op| PATH_PARAM.find_iter(op.path).count())
+    }
+
+    /// Validate a response to an operation, which must have a status, headers and content declared
+    /// for the operation, where a JSON body must conform to the schema of the content
+    pub fn conformance(
+        &self,
+        op: &Operation,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Vec<Outcome> {
+        let description: String = format!("response conforms to {op}");
+        let violations: Vec<String> = self.violations(op, status, headers, body);
+        if violations.is_empty() {
+            return vec![Outcome::contract(description, None)];
+        }
+        violations
+            .into_iter()
+            .map(|v| Outcome::contract(description.clone(), Some(v)))
+            .collect()
+    }
+
+    fn violations(
+        &self,
+        op: &Operation,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &str,
+    ) -> Vec<String> {
+        let responses: Option<&Mapping> = op.get("responses").and_then(Value::as_mapping);
+        let code: String = status.as_u16().to_string();
+        let class: String = format!("{}XX", status.as_u16() / 100);
+        let response: Option<&Value> = [code.as_str(), class.as_str(), "default"]
+            .iter()
+            .find_map(|key| {
+                let mut responses = responses.into_iter().flatten();
+                responses.find(|(k, _)| scalar(k).is_some_and(|k| k.eq_ignore_ascii_case(key)))
+            })
+            .map(|(_, response)| self.resolve(response));
+        let response: &Value = match response {
+            Some(response) => response,
+            None => return vec![format!("status {code} is not declared")],
+        };
+
+        let validator = Validator::new(&self.json);
+        let mut violations: Vec<String> = Vec::new();
+
+        let declared = response.get("headers").and_then(Value::as_mapping).into_iter().flatten();
+        for (name, header) in
+            declared.filter_map(|(name, h)| Some((name.as_str()?, self.resolve(h))))
+        {
+            let required: bool = header.get("required").and_then(Value::as_bool).unwrap_or(false);
+            let value: Option<&str> = headers.get(name).and_then(|v| v.to_str().ok());
+            match (value, header.get("schema")) {
+                (None, _) if required => violations.push(format!("header {name} is missing")),
+                (Some(value), Some(schema)) => {
+                    let value: serde_json::Value = coerce(value, self.resolve(schema));
+                    let schema: serde_json::Value =
+                        serde_json::to_value(schema).unwrap_or_default();
+                    violations.extend(
+                        validator
+                            .validate(&schema, &value)
+                            .iter()
+                            .map(|v| format!("header {name}: {v}")),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        let content: Option<&Mapping> = response.get("content").and_then(Value::as_mapping);
+        let content_type: Option<&str> = headers
+            .get("content-type")
+            .and_then(|ct| ct.to_str().ok())
+            .and_then(|ct| ct.split(';').next())
+            .map(str::trim);
+        let (content, content_type): (&Mapping, &str) = match (content, content_type) {
+            (Some(content), Some(content_type)) if !content.is_empty() => (content, content_type),
+            _ => return violations,
+        };
+
+        let (actual_kind, actual_subtype) =
+            content_type.split_once('/').unwrap_or((content_type, ""));
+        let range = |key: &str| {
+            let (kind, subtype) = key.split_once('/').unwrap_or((key, "*"));
+            (kind == "*" || kind.eq_ignore_ascii_case(actual_kind))
+                && (subtype == "*" || subtype.eq_ignore_ascii_case(actual_subtype))
+        };
+        let media_types = || content.iter().filter_map(|(key, media)| Some((key.as_str()?, media)));
+        let media: Option<&Value> = media_types()
+            .find(|(key, _)| key.eq_ignore_ascii_case(content_type))
+            .or_else(|| media_types().find(|(key, _)| range(key)))
+            .map(|(_, media)| media);
+        let media: &Value = match media {
+            Some(media) => self.resolve(media),
+            None => {
+                violations.push(format!("content type {content_type} is not declared"));
+                return violations;
+            }
+        };
+
+        if let Some(schema) = media.get("schema").filter(|_| content_type.contains("json")) {
+            match serde_json::from_str::<serde_json::Value>(body) {
+                Ok(json) => {
+                    let schema: serde_json::Value =
+                        serde_json::to_value(schema).unwrap_or_default();
+                    violations
+                        .extend(validator.validate(&schema, &json).iter().map(|v| v.to_string()))
+                }
+                Err(e) => violations.push(format!("body is not JSON: {e}")),
+            }
+        }
+
+        violations
+    }
+
+    pub fn operations(&self) -> Vec<Operation<'_>> {
+        let paths: Option<&Mapping> = self.root.get("paths").and_then(Value::as_mapping);
+        paths
+            .into_iter()
+            .flatten()
+            .filter_map(|(path, item)| Some((path.as_str()?, self.resolve(item))))
+            .flat_map(|(path, item)| {
+                METHODS.iter().filter_map(move |method| {
+                    let operation: &Value = item.get(method)?;
+                    Some(Operation {
+                        method,
+                        path,
+                        item,
+                        operation: self.resolve(operation),
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Follow a reference (`$ref`) to another part of the same document, if the value is one
+    pub fn resolve<'a>(&'a self, value: &'a Value) -> &'a Value {
+        let mut value: &Value = value;
+        for _ in 0..MAX_DEPTH {
+            let reference: &str = match value.get("$ref").and_then(Value::as_str) {
+                Some(reference) => reference,
+                None => return value,
+            };
+            match self.pointer(reference) {
+                Some(target) => value = target,
+                None => {
+                    log::warn!("Unable to resolve reference {reference}");
+                    return value;
+                }
+            }
+        }
+        value
+    }
+
+    fn pointer(&self, reference: &str) -> Option<&Value> {
+        let pointer: &str = reference.strip_prefix('#')?;
+        pointer
+            .split('/')
+            .skip(1)
+            .map(|token| token.replace("~1", "/").replace("~0", "~"))
+            .try_fold(&self.root, |value, token| match value {
+                Value::Sequence(seq) => seq.get(token.parse::<usize>().ok()?),
+                value => value.get(token.as_str()),
+            })
+    }
+
+    /// The parameters of an operation, including those declared for all operations of its path,
+    /// unless overridden by the operation
+    pub fn parameters<'a>(&'a self, op: &Operation<'a>) -> Vec<&'a Value> {
+        let declared = |value: &'a Value| -> Vec<&'a Value> {
+            value
+                .get("parameters")
+                .and_then(Value::as_sequence)
+                .into_iter()
+                .flatten()
+                .map(|p| self.resolve(p))
+                .collect()
+        };
+        let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+        let own: Vec<&Value> = declared(op.operation);
+        let inherited: Vec<&Value> = declared(op.item)
+            .into_iter()
+            .filter(|p| !own.iter().any(|o| key(o) == key(p)))
+            .collect();
+        inherited.into_iter().chain(own).collect()
+    }
+
+    /// An example value of a schema, which is the example or default value given in the schema, or
+    /// otherwise a value generated from its type. Properties of an object that refer back to a
+    /// schema that is already being generated are left out.
+    pub fn example(&self, schema: &Value) -> Value {
+        self.example_in(schema, &mut Vec::new())
+    }
+
+    fn example_in<'a>(&'a self, schema: &'a Value, refs: &mut Vec<&'a str>) -> Value {
+        let reference: Option<&str> = schema.get("$ref").and_then(Value::as_str);
+        if let Some(reference) = reference {
+            if refs.contains(&reference) || refs.len() >= MAX_DEPTH {
+                return Value::Null;
+            }
+            refs.push(reference);
+        }
+        let example: Value = self.generate(self.resolve(schema), refs);
+        if reference.is_some() {
+            refs.pop();
+        }
+        example
+    }
+
+    fn generate<'a>(&'a self, schema: &'a Value, refs: &mut Vec<&'a str>) -> Value {
+        if let Some(example) = schema.get("example").or_else(|| schema.get("default")) {
+            return example.clone();
+        }
+        if let Some(first) = schema.get("enum").and_then(|e| e.get(0)) {
+            return first.clone();
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_sequence) {
+            let mut merged = Mapping::new();
+            for schema in all {
+                if let Value::Mapping(example) = self.example_in(schema, refs) {
+                    merged.extend(example);
+                }
+            }
+            return Value::Mapping(merged);
+        }
+        let alternatives = schema.get("oneOf").or_else(|| schema.get("anyOf"));
+        if let Some(first) = alternatives.and_then(|a| a.get(0)) {
+            return self.example_in(first, refs);
+        }
+
+        let kind: Option<&str> = schema.get("type").and_then(|t| match t {
+            Value::Sequence(types) => types.iter().filter_map(Value::as_str).find(|t| *t != "null"),
+            t => t.as_str(),
+        });
+        let format: Option<&str> = schema.get("format").and_then(Value::as_str);
+        match kind {
+            Some("object") | None if schema.get("properties").is_some() => {
+                let properties: Option<&Mapping> =
+                    schema.get("properties").and_then(Value::as_mapping);
+                let mut example = Mapping::new();
+                for (name, schema) in properties.into_iter().flatten() {
+                    match self.example_in(schema, refs) {
+                        Value::Null => {}
+                        value => {
+                            example.insert(name.clone(), value);
+                        }
+                    }
+                }
+                Value::Mapping(example)
+            }
+            Some("object") => Value::Mapping(Mapping::new()),
+            Some("array") => match schema.get("items") {
+                Some(items) => match self.example_in(items, refs) {
+                    Value::Null => Value::Sequence(Vec::new()),
+                    item => Value::Sequence(vec![item]),
+                },
+                None => Value::Sequence(Vec::new()),
+            },
+            Some("integer") => 0.into(),
+            Some("number") => 0.0.into(),
+            Some("boolean") => true.into(),
+            Some("string") => match format {
+                Some("date-time") => "2024-01-01T00:00:00Z",
+                Some("date") => "2024-01-01",
+                Some("email") => "user@example.com",
+                Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                Some("uri") => "https://example.com",
+                _ => "string",
+            }
+            .into(),
+            _ => Value::Null,
+        }
+    }
+}
+
+impl Operation<'_> {
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.operation.get(key)
+    }
+}
+
+impl Display for Operation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.method.to_uppercase(), self.path)
+    }
+}
+
+/// The value of a header as JSON, parsed according to the type of its schema
+fn coerce(value: &str, schema: &Value) -> serde_json::Value {
+    let parsed: Option<serde_json::Value> = match schema.get("type").and_then(Value::as_str) {
+        Some("integer" | "number" | "boolean") => serde_json::from_str(value).ok(),
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| serde_json::Value::String(value.to_string()))
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Convert every operation of an OpenAPI 3 document into a request file, in a directory named
+/// after the first tag of the operation. The URL of the first server is written to `.env`, as the
+/// variable `base_url`.
+pub fn convert(spec: &str) -> Result<Import, ImportError> {
+    let spec: Spec = Spec::parse(spec).map_err(ImportError::Parse)?;
+
+    let mut import = Import::default();
+    for op in spec.operations() {
+        let tag: Option<&str> = op.get("tags").and_then(|t| t.get(0)).and_then(Value::as_str);
+        let dir: PathBuf = tag.map(import::slug).map(PathBuf::from).unwrap_or_default();
+        let name: String = match op.get("operationId").and_then(Value::as_str) {
+            Some(id) => id.to_string(),
+            None => format!("{} {}", op.method, op.path),
+        };
+        let comment = op.get("summary").or_else(|| op.get("description")).and_then(Value::as_str);
+        import.request(&dir, &name, comment, convert_operation(&spec, &op));
+    }
+
+    let server: String = spec.server().unwrap_or_else(|| String::from("http://localhost"));
+    import.env(PathBuf::from(".env"), vec![(String::from(BASE_URL), server)]);
+
+    Ok(import)
+}
+
+fn convert_operation(spec: &Spec, op: &Operation) -> Mapping {
+    let mut mapping = Mapping::new();
+    mapping.insert("method".into(), op.method.to_uppercase().into());
+
+    let path: String = PATH_PARAM
+        .replace_all(op.path, |caps: &Captures| {
+            format!("{{{{{}}}}}", import::variable_name(&caps[1]))
+        })
+        .to_string();
+    mapping.insert("url".into(), format!("{{{{{BASE_URL}}}}}{path}").into());
+
+    let mut query: Vec<(String, String)> = Vec::new();
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for param in spec.parameters(op) {
+        let name: &str = match param.get("name").and_then(Value::as_str) {
+            Some(name) => name,
+            None => continue,
+        };
+        let required: bool = param.get("required").and_then(Value::as_bool).unwrap_or(false);
+        let value: Option<String> = if required {
+            Some(format!("{{{{{}}}}}", import::variable_name(name)))
+        } else {
+            let example = param.get("example").or_else(|| {
+                let schema: &Value = spec.resolve(param.get("schema")?);
+                schema.get("example").or_else(|| schema.get("default"))
+            });
+            example.and_then(scalar)
+        };
+        let value: String = match value {
+            Some(value) => value,
+            None => continue,
+        };
+        match param.get("in").and_then(Value::as_str) {
+            Some("query") => query.push((name.to_string(), value)),
+            Some("header") if !IGNORED_HEADERS.contains(&name.to_lowercase().as_str()) => {
+                headers.push((name.to_string(), value))
+            }
+            _ => {}
+        }
+    }
+
+    security(spec, op, &mut headers, &mut query);
+
+    if !query.is_empty() {
+        mapping.insert("query".into(), import::params(query));
+    }
+
+    let body: Option<(&str, &str, Value)> = op.get("requestBody").and_then(|b| body(spec, b));
+    if let Some((mime_type, "body", _)) = body {
+        headers.push((String::from("Content-Type"), mime_type.to_string()));
+    }
+
+    if !headers.is_empty() {
+        let headers: Mapping = headers.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        mapping.insert("headers".into(), Value::Mapping(headers));
+    }
+
+    if let Some((_, key, value)) = body {
+        mapping.insert(key.into(), value);
+    }
+
+    mapping
+}
+
+/// Add headers or query parameters for the first security requirement of an operation, for HTTP
+/// bearer authentication and API keys
+fn security(
+    spec: &Spec,
+    op: &Operation,
+    headers: &mut Vec<(String, String)>,
+    query: &mut Vec<(String, String)>,
+) {
+    let requirements: Option<&Value> = op.get("security").or_else(|| spec.root.get("security"));
+    let requirement: Option<&Mapping> =
+        requirements.and_then(|r| r.get(0)).and_then(Value::as_mapping);
+    let schemes = requirement.into_iter().flat_map(Mapping::keys).filter_map(Value::as_str);
+
+    for name in schemes {
+        let scheme: Option<&Value> = spec
+            .root
+            .get("components")
+            .and_then(|c| c.get("securitySchemes"))
+            .and_then(|s| s.get(name))
+            .map(|s| spec.resolve(s));
+        let scheme: &Value = match scheme {
+            Some(scheme) => scheme,
+            None => continue,
+        };
+
+        let get = |key: &str| scheme.get(key).and_then(Value::as_str);
+        match (get("type"), get("scheme").map(str::to_lowercase).as_deref()) {
+            (Some("http"), Some("bearer")) | (Some("oauth2" | "openIdConnect"), _) => {
+                headers.push((String::from("Authorization"), String::from("Bearer {{token}}")))
+            }
+            (Some("apiKey"), _) => {
+                let key: &str = get("name").unwrap_or(name);
+                let value: String = format!("{{{{{}}}}}", import::variable_name(key));
+                match get("in") {
+                    Some("query") => query.push((key.to_string(), value)),
+                    Some("header") => headers.push((key.to_string(), value)),
+                    _ => log::warn!("API key {name} is only supported in a header or query"),
+                }
+            }
+            _ => log::warn!("Security scheme {name} is not supported and was left out"),
+        }
+    }
+}
+
+/// The content type and body of a request, with an example from the request body of an operation,
+/// preferring JSON if the operation accepts several types of content
+fn body<'a>(spec: &'a Spec, request_body: &'a Value) -> Option<(&'a str, &'static str, Value)> {
+    let content: &Mapping = spec.resolve(request_body).get("content")?.as_mapping()?;
+    let media_types: Vec<(&str, &Value)> =
+        content.iter().filter_map(|(k, v)| Some((k.as_str()?, v))).collect();
+    let (mime_type, media) = media_types
+        .iter()
+        .find(|(mime_type, _)| mime_type.contains("json"))
+        .or_else(|| media_types.first())?;
+
+    let example: Value = media
+        .get("example")
+        .cloned()
+        .or_else(|| {
+            let examples: &Mapping = media.get("examples")?.as_mapping()?;
+            let (_, first) = examples.iter().next()?;
+            spec.resolve(first).get("value").cloned()
+        })
+        .or_else(|| media.get("schema").map(|schema| spec.example(schema)))?;
+
+    let (key, value): (&str, Value) = match *mime_type {
+        mime_type if mime_type.contains("json") => ("json", example),
+        "application/x-www-form-urlencoded" => ("form", fields(example)),
+        "multipart/form-data" => ("multipart", fields(example)),
+        _ => match example {
+            Value::Null => return None,
+            example => ("body", scalar(&example).unwrap_or_default().into()),
+        },
+    };
+    Some((mime_type, key, value))
+}
+
+/// Fields of a form, where every value is a string
+fn fields(example: Value) -> Value {
+    let fields: Mapping = example
+        .as_mapping()
+        .into_iter()
+        .flatten()
+        .map(|(k, v)| (k.clone(), scalar(v).unwrap_or_default().into()))
+        .collect();
+    Value::Mapping(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use reqwest::{StatusCode, Url};
+
+    use super::{convert, Spec};
+    use crate::expect::Outcome;
+
+    #[test]
+    fn test_convert_openapi_document() {
+        let spec = r##"
+openapi: 3.0.3
+servers:
+  - url: https://{env}.example.com/v1/
+    variables:
+      env:
+        default: api
+security:
+  - bearer: []
+paths:
+  /pets/{petId}:
+    parameters:
+      - $ref: "#/components/parameters/PetId"
+    put:
+      tags: [Pets]
+      operationId: updatePet
+      summary: Update a pet
+      parameters:
+        - name: X-Request-Id
+          in: header
+          required: true
+        - name: dry_run
+          in: query
+          schema:
+            type: boolean
+            default: false
+        - name: verbose
+          in: query
+      requestBody:
+        content:
+          application/xml: {}
+          application/json:
+            schema:
+              $ref: "#/components/schemas/Pet"
+components:
+  parameters:
+    PetId:
+      name: petId
+      in: path
+      required: true
+  securitySchemes:
+    bearer:
+      type: http
+      scheme: bearer
+  schemas:
+    Pet:
+      type: object
+      properties:
+        name:
+          type: string
+          example: Rex
+        born:
+          type: string
+          format: date
+        tags:
+          type: array
+          items:
+            type: string
+        owner:
+          $ref: "#/components/schemas/Pet"
+"##;
+
+        let import = convert(spec).unwrap();
+        let doc = &import.requests()[0];
+        assert_eq!(Path::new("pets/updatepet"), doc.path);
+        assert_eq!(Some("Update a pet"), doc.comment.as_deref());
+        assert_eq!(doc.request["method"], "PUT");
+        assert_eq!(doc.request["url"], "{{base_url}}/pets/{{petId}}");
+        assert_eq!(doc.request["query"]["dry_run"], "false");
+        assert!(doc.request["query"].get("verbose").is_none());
+        assert_eq!(doc.request["headers"]["X-Request-Id"], "{{X-Request-Id}}");
+        assert_eq!(doc.request["headers"]["Authorization"], "Bearer {{token}}");
+        assert_eq!(doc.request["json"]["name"], "Rex");
+        assert_eq!(doc.request["json"]["born"], "2024-01-01");
+        assert_eq!(doc.request["json"]["tags"][0], "string");
+        assert!(doc.request["json"].get("owner").is_none());
+
+        let env = &import.envs()[0];
+        assert_eq!(("base_url".to_string(), "https://api.example.com/v1".to_string()), env.vars[0]);
+    }
+
+    #[test]
+    fn test_validate_response() {
+        let spec = Spec::parse(
+            r##"
+openapi: 3.0.0
+servers:
+  - url: https://example.com/v1
+paths:
+  /pets/{petId}:
+    get:
+      responses:
+        200:
+          headers:
+            X-Rate-Limit:
+              required: true
+              schema:
+                type: integer
+          content:
+            application/json:
+              schema:
+                $ref: "#/components/schemas/Pet"
+  /pets/mine:
+    get:
+      responses:
+        default:
+          description: Any response
+components:
+  schemas:
+    Pet:
+      type: object
+      required: [id]
+      properties:
+        id:
+          type: integer
+        tag:
+          type: string
+          nullable: true
+"##,
+        )
+        .unwrap();
+
+        let url = Url::parse("https://example.com/v1/pets/12").unwrap();
+        let op = spec.find("GET", &url).unwrap();
+        assert_eq!("GET /pets/{petId}", op.to_string());
+        let mine = Url::parse("https://example.com/v1/pets/mine").unwrap();
+        assert_eq!("/pets/mine", spec.find("GET", &mine).unwrap().path);
+        assert!(spec.find("POST", &url).is_none());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("application/json; charset=utf-8"));
+        headers.insert("x-rate-limit", HeaderValue::from_static("many"));
+        let body = r#"{"id": "12", "tag": null}"#;
+
+        let outcomes: Vec<Outcome> = spec.conformance(&op, StatusCode::OK, &headers, body);
+        let failures: Vec<&str> = outcomes.iter().filter_map(Outcome::failure).collect();
+        assert_eq!(
+            vec![
+                "header X-Rate-Limit: (root): expected integer, found string (type)",
+                "/id: expected integer, found string (type)"
+            ],
+            failures
+        );
+
+        let outcomes = spec.conformance(&op, StatusCode::NOT_FOUND, &headers, body);
+        assert_eq!(Some("status 404 is not declared"), outcomes[0].failure());
+    }
+}
@@ -0,0 +1,93 @@
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+/// An ordered list of key/value pairs, such as query parameters.
+///
+/// Can be written either as a mapping, where a list value repeats the key once per item, or as a
+/// list of single entry mappings when the same key has to be repeated in a specific order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Value")]
+pub struct Params(Vec<(String, String)>);
+
+impl Params {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (String, String)> {
+        self.0.iter()
+    }
+
+    fn extend_from_mapping(&mut self, mapping: Mapping) -> Result<(), String> {
+        for (key, value) in mapping {
+            let key: String = scalar(key)?;
+            match value {
+                Value::Sequence(values) => {
+                    for value in values {
+                        self.0.push((key.clone(), scalar(value)?));
+                    }
+                }
+                value => self.0.push((key, scalar(value)?)),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<Value> for Params {
+    type Error = String;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let mut params = Params::default();
+        match value {
+            Value::Null => {}
+            Value::Mapping(mapping) => params.extend_from_mapping(mapping)?,
+            Value::Sequence(entries) => {
+                for entry in entries {
+                    match entry {
+                        Value::Mapping(mapping) => params.extend_from_mapping(mapping)?,
+                        other => return Err(format!("Expected a key/value pair, got {:?}", other)),
+                    }
+                }
+            }
+            other => return Err(format!("Expected a mapping or a list, got {:?}", other)),
+        }
+        Ok(params)
+    }
+}
+
+pub fn scalar(value: Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Null => Ok(String::new()),
+        other => Err(format!("Expected a single value, got {:?}", other)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Params;
+
+    #[test]
+    fn test_parse_params_from_mapping_and_list() {
+        let mapping: Params = serde_yaml::from_str("tag: [a, b]\npage: 2\nq: hello").unwrap();
+        let list: Params = serde_yaml::from_str("- tag: a\n- page: 2\n- tag: b").unwrap();
+
+        let mapping: Vec<(String, String)> = mapping.iter().cloned().collect();
+        let list: Vec<(String, String)> = list.iter().cloned().collect();
+
+        assert_eq!(
+            vec![
+                ("tag".to_string(), "a".to_string()),
+                ("tag".to_string(), "b".to_string()),
+                ("page".to_string(), "2".to_string()),
+                ("q".to_string(), "hello".to_string()),
+            ],
+            mapping
+        );
+        assert_eq!("b", list[2].1);
+        assert_eq!("page", list[1].0);
+    }
+}
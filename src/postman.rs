@@ -0,0 +1,470 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::import::{self, Import, ImportError};
+
+/// A collection in the Postman v2.1 format
+#[derive(Debug, Deserialize)]
+struct Collection {
+    #[serde(default)]
+    item: Vec<Item>,
+    #[serde(default)]
+    variable: Vec<KeyValue>,
+    auth: Option<Auth>,
+}
+
+/// A request, or a folder of requests
+#[derive(Debug, Deserialize)]
+struct Item {
+    name: String,
+    description: Option<Description>,
+    request: Option<Request>,
+    #[serde(default)]
+    item: Vec<Item>,
+    auth: Option<Auth>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Description {
+    Text(String),
+    Content { content: String },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Request {
+    Url(String),
+    Request(Box<RequestDetails>),
+}
+
+#[derive(Debug, Deserialize)]
+struct RequestDetails {
+    method: Option<String>,
+    #[serde(default)]
+    header: Vec<KeyValue>,
+    url: Option<Url>,
+    body: Option<Body>,
+    auth: Option<Auth>,
+    description: Option<Description>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Url {
+    Raw(String),
+    Url { raw: String },
+}
+
+#[derive(Debug, Deserialize)]
+struct Body {
+    mode: Option<String>,
+    raw: Option<String>,
+    #[serde(default)]
+    urlencoded: Vec<KeyValue>,
+    #[serde(default)]
+    formdata: Vec<KeyValue>,
+    file: Option<FileSrc>,
+    graphql: Option<GraphQl>,
+    options: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FileSrc {
+    src: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQl {
+    query: String,
+    variables: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct Auth {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    bearer: Vec<KeyValue>,
+    #[serde(default)]
+    basic: Vec<KeyValue>,
+    #[serde(default)]
+    apikey: Vec<KeyValue>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct KeyValue {
+    key: String,
+    value: Option<serde_json::Value>,
+    #[serde(default)]
+    disabled: bool,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+    src: Option<serde_json::Value>,
+}
+
+/// An environment exported from Postman
+#[derive(Debug, Deserialize)]
+struct Environment {
+    name: String,
+    #[serde(default)]
+    values: Vec<EnvValue>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EnvValue {
+    key: String,
+    value: Option<serde_json::Value>,
+    #[serde(default = "enabled")]
+    enabled: bool,
+    #[serde(rename = "type")]
+    kind: Option<String>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Convert a Postman collection, and any environments exported from Postman, into request files
+/// in a directory per folder. Collection variables are written to `.env`, and the variables of
+/// each environment to `<environment>.env`, or `<environment>.sec` for secrets.
+pub fn convert(collection: &str, environments: &[String]) -> Result<Import, ImportError> {
+    let collection: Collection = serde_json::from_str(collection)
+        .map_err(|e| ImportError::Parse(format!("Invalid Postman collection: {e}")))?;
+
+    let mut import = Import::default();
+    for item in &collection.item {
+        add_item(&mut import, Path::new(""), item, collection.auth.as_ref());
+    }
+
+    let vars: Vec<(String, String)> = collection
+        .variable
+        .iter()
+        .filter(|kv| !kv.disabled)
+        .map(|kv| (kv.key.clone(), kv.value()))
+        .collect();
+    import.env(PathBuf::from(".env"), vars);
+
+    for environment in environments {
+        let environment: Environment = serde_json::from_str(environment)
+            .map_err(|e| ImportError::Parse(format!("Invalid Postman environment: {e}")))?;
+        let name: String = import::slug(&environment.name);
+        let (secrets, vars): (Vec<&EnvValue>, Vec<&EnvValue>) = environment
+            .values
+            .iter()
+            .filter(|v| v.enabled)
+            .partition(|v| v.kind.as_deref() == Some("secret"));
+        import.env(PathBuf::from(format!("{name}.env")), pairs(vars));
+        import.env(PathBuf::from(format!("{name}.sec")), pairs(secrets));
+    }
+
+    Ok(import)
+}
+
+fn pairs(values: Vec<&EnvValue>) -> Vec<(String, String)> {
+    values
+        .into_iter()
+        .map(|v| (v.key.clone(), template(&string(v.value.as_ref()))))
+        .collect()
+}
+
+fn add_item(import: &mut Import, dir: &Path, item: &Item, auth: Option<&Auth>) {
+    let auth: Option<&Auth> = match &item.auth {
+        Some(auth) if auth.kind != "inherit" => Some(auth),
+        _ => auth,
+    };
+
+    match &item.request {
+        Some(request) => {
+            let (request, description): (Mapping, Option<&Description>) =
+                convert_request(request, auth);
+            let comment: Option<&str> =
+                description.or(item.description.as_ref()).map(Description::text);
+            import.request(dir, &item.name, comment, request);
+        }
+        None => {
+            let dir: PathBuf = dir.join(import::slug(&item.name));
+            for child in &item.item {
+                add_item(import, &dir, child, auth);
+            }
+        }
+    }
+}
+
+fn convert_request<'a>(
+    request: &'a Request,
+    auth: Option<&Auth>,
+) -> (Mapping, Option<&'a Description>) {
+    let mut mapping = Mapping::new();
+    let request: &RequestDetails = match request {
+        Request::Url(url) => {
+            mapping.insert("method".into(), "GET".into());
+            mapping.insert("url".into(), template(url).into());
+            return (mapping, None);
+        }
+        Request::Request(request) => request,
+    };
+
+    let url: &str = match &request.url {
+        Some(Url::Raw(raw)) | Some(Url::Url { raw }) => raw,
+        None => "",
+    };
+    mapping.insert(
+        "method".into(),
+        request.method.as_deref().unwrap_or("GET").to_uppercase().into(),
+    );
+    mapping.insert("url".into(), template(url).into());
+
+    let mut headers: Vec<(String, String)> = request
+        .header
+        .iter()
+        .filter(|kv| !kv.disabled)
+        .map(|kv| (kv.key.clone(), template(&kv.value())))
+        .collect();
+    let mut query: Vec<(String, String)> = Vec::new();
+
+    let auth: Option<&Auth> = match &request.auth {
+        Some(auth) if auth.kind != "inherit" => Some(auth),
+        _ => auth,
+    };
+    if let Some(auth) = auth {
+        apply_auth(auth, &mut headers, &mut query);
+    }
+
+    if !query.is_empty() {
+        mapping.insert("query".into(), import::params(query));
+    }
+
+    let mut body: Option<(&str, Value)> = None;
+    if let Some(request_body) = &request.body {
+        let content_type: Option<String> = headers
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case("content-type"))
+            .map(|(_, v)| v.clone());
+        if let Some((key, value)) = convert_body(request_body, content_type.as_deref()) {
+            // The content type of a multipart body includes the boundary, so it must be left out
+            if key == "multipart" {
+                headers.retain(|(k, _)| !k.eq_ignore_ascii_case("content-type"));
+            }
+            body = Some((key, value));
+        }
+    }
+
+    if !headers.is_empty() {
+        let headers: Mapping = headers.into_iter().map(|(k, v)| (k.into(), v.into())).collect();
+        mapping.insert("headers".into(), Value::Mapping(headers));
+    }
+
+    if let Some((key, value)) = body {
+        mapping.insert(key.into(), value);
+    }
+
+    (mapping, request.description.as_ref())
+}
+
+fn apply_auth(auth: &Auth, headers: &mut Vec<(String, String)>, query: &mut Vec<(String, String)>) {
+    let param = |params: &[KeyValue], key: &str| -> Option<String> {
+        params.iter().find(|kv| kv.key == key).map(|kv| template(&kv.value()))
+    };
+
+    match auth.kind.as_str() {
+        "noauth" => {}
+        "bearer" => {
+            let token: String = param(&auth.bearer, "token").unwrap_or_default();
+            headers.push((String::from("Authorization"), format!("Bearer {token}")));
+        }
+        "basic" => {
+            let username: String = param(&auth.basic, "username").unwrap_or_default();
+            let password: String = param(&auth.basic, "password").unwrap_or_default();
+            let credentials: String = format!("{username}:{password}");
+            if credentials.contains("{{") {
+                log::warn!("Basic authentication with variables is not supported and was left out");
+            } else {
+                let credentials: String = base64::encode(credentials);
+                headers.push((String::from("Authorization"), format!("Basic {credentials}")));
+            }
+        }
+        "apikey" => {
+            let key: String = param(&auth.apikey, "key").unwrap_or_default();
+            let value: String = param(&auth.apikey, "value").unwrap_or_default();
+            match param(&auth.apikey, "in").as_deref() {
+                Some("query") => query.push((key, value)),
+                _ => headers.push((key, value)),
+            }
+        }
+        kind => log::warn!("Authentication of type {kind} is not supported and was left out"),
+    }
+}
+
+fn convert_body(body: &Body, content_type: Option<&str>) -> Option<(&'static str, Value)> {
+    let enabled = |params: &[KeyValue]| -> Vec<(String, String)> {
+        params
+            .iter()
+            .filter(|kv| !kv.disabled)
+            .map(|kv| (kv.key.clone(), template(&kv.value())))
+            .collect()
+    };
+
+    match body.mode.as_deref()? {
+        "raw" => {
+            let raw: &str = body.raw.as_deref().filter(|raw| !raw.is_empty())?;
+            let language: Option<&str> = body
+                .options
+                .as_ref()
+                .and_then(|options| options.pointer("/raw/language"))
+                .and_then(serde_json::Value::as_str);
+            let content_type: Option<&str> = match language {
+                Some("json") => Some("application/json"),
+                _ => content_type.or(Some("text/plain")),
+            };
+            Some(import::text_body(&template(raw), content_type))
+        }
+        "urlencoded" => Some(("form", import::params(enabled(&body.urlencoded)))),
+        "formdata" => {
+            let parts: Mapping = body
+                .formdata
+                .iter()
+                .filter(|kv| !kv.disabled)
+                .map(|kv| {
+                    let part: Value = match (kv.kind.as_deref(), &kv.src) {
+                        (Some("file"), Some(src)) => {
+                            let mut part = Mapping::new();
+                            part.insert("file".into(), string(Some(src)).into());
+                            Value::Mapping(part)
+                        }
+                        _ => template(&kv.value()).into(),
+                    };
+                    (kv.key.as_str().into(), part)
+                })
+                .collect();
+            Some(("multipart", Value::Mapping(parts)))
+        }
+        "file" => {
+            let src: &str = body.file.as_ref()?.src.as_deref()?;
+            Some(("body_file", src.into()))
+        }
+        "graphql" => {
+            let graphql: &GraphQl = body.graphql.as_ref()?;
+            let mut json = serde_json::Map::new();
+            json.insert("query".into(), template(&graphql.query).into());
+            if let Some(variables) = &graphql.variables {
+                if let Ok(variables) = serde_json::from_str(&template(variables)) {
+                    json.insert("variables".into(), variables);
+                }
+            }
+            Some(("json", serde_yaml::to_value(json).unwrap()))
+        }
+        mode => {
+            log::warn!("Body of type {mode} is not supported and was left out");
+            None
+        }
+    }
+}
+
+impl KeyValue {
+    fn value(&self) -> String {
+        string(self.value.as_ref())
+    }
+}
+
+impl Description {
+    fn text(&self) -> &str {
+        match self {
+            Description::Text(text) => text,
+            Description::Content { content } => content,
+        }
+    }
+}
+
+fn string(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(serde_json::Value::Array(values)) => string(values.first()),
+        Some(other) => other.to_string(),
+    }
+}
+
+fn template(input: &str) -> String {
+    import::variables(input, |name| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::convert;
+
+    #[test]
+    fn test_convert_postman_collection() {
+        let collection = r#"{
+            "info": { "name": "Pets", "schema": "https://schema.getpostman.com/json/collection/v2.1.0/collection.json" },
+            "auth": { "type": "bearer", "bearer": [{ "key": "token", "value": "{{api token}}" }] },
+            "variable": [{ "key": "base_url", "value": "https://example.com" }],
+            "item": [
+                {
+                    "name": "Pets",
+                    "item": [{
+                        "name": "Create pet",
+                        "request": {
+                            "method": "POST",
+                            "description": "Creates a new pet",
+                            "header": [
+                                { "key": "Content-Type", "value": "application/json" },
+                                { "key": "X-Old", "value": "1", "disabled": true }
+                            ],
+                            "url": { "raw": "{{base_url}}/pets", "host": ["{{base_url}}"], "path": ["pets"] },
+                            "body": { "mode": "raw", "raw": "{\"name\": \"{{name}}\"}" }
+                        }
+                    }]
+                },
+                {
+                    "name": "Log in",
+                    "request": {
+                        "method": "POST",
+                        "auth": { "type": "noauth" },
+                        "url": "{{base_url}}/login",
+                        "body": { "mode": "urlencoded", "urlencoded": [{ "key": "user", "value": "tom" }] }
+                    }
+                }
+            ]
+        }"#;
+
+        let environment = r#"{
+            "name": "Staging",
+            "values": [
+                { "key": "base_url", "value": "https://staging.example.com", "enabled": true },
+                { "key": "api token", "value": "s3cret", "type": "secret", "enabled": true }
+            ]
+        }"#;
+
+        let import = convert(collection, &[environment.to_string()]).unwrap();
+        let requests = import.requests();
+
+        assert_eq!(Path::new("pets/create_pet"), requests[0].path);
+        let create = &requests[0].request;
+        assert_eq!(create["url"], "{{base_url}}/pets");
+        assert_eq!(create["headers"]["Authorization"], "Bearer {{api_token}}");
+        assert!(create["headers"].get("X-Old").is_none());
+        assert_eq!(create["json"]["name"], "{{name}}");
+        assert!(requests[0].to_yaml().starts_with("# Creates a new pet\n"));
+
+        assert_eq!(Path::new("log_in"), requests[1].path);
+        let login = &requests[1].request;
+        assert!(login.get("headers").is_none());
+        assert_eq!(login["form"]["user"], "tom");
+
+        let envs: Vec<&Path> = import.envs().iter().map(|e| e.path.as_path()).collect();
+        assert_eq!(
+            vec![
+                Path::new(".env"),
+                Path::new("staging.env"),
+                Path::new("staging.sec")
+            ],
+            envs
+        );
+        assert_eq!(("api_token".to_string(), "s3cret".to_string()), import.envs()[2].vars[0]);
+    }
+}
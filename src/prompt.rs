@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::prop::{Property, Source};
+use crate::redact;
+
+lazy_static! {
+    /// Values given for variables earlier in this run, so each variable is only prompted for once
+    static ref ANSWERS: Mutex<Vec<Property>> = Mutex::new(Vec::new());
+}
+
+/// Whether it is possible to prompt for values, which requires stdin and stderr to be a terminal
+pub fn is_interactive() -> bool {
+    io::stdin().is_terminal() && io::stderr().is_terminal()
+}
+
+/// Values for variables without a value, which were given earlier in this run or are prompted for.
+/// Input is hidden for variables with a name that looks like a secret, such as `API_TOKEN`, or
+/// which are in `secrets`. Once all values are given, they can optionally be saved to a file.
+pub fn values(names: &[String], secrets: &HashSet<String>) -> io::Result<Vec<Property>> {
+    let mut answers = ANSWERS.lock().unwrap();
+    let mut values: Vec<Property> =
+        answers.iter().filter(|p| names.iter().any(|n| n == p.key())).cloned().collect();
+    let missing: Vec<&String> =
+        names.iter().filter(|n| !values.iter().any(|p| p.key() == *n)).collect();
+    if missing.is_empty() {
+        return Ok(values);
+    }
+
+    let names: Vec<&str> = missing.iter().map(|name| name.as_str()).collect();
+    eprintln!("Missing values for variables: {}", names.join(", "));
+
+    let mut prompted: Vec<Property> = Vec::with_capacity(missing.len());
+    for name in missing {
+        let secret: bool = secrets.contains(name) || redact::is_secret_name(name);
+        let value: String = ask(&format!("{name}: "), secret)?;
+        let prop = Property::new(name.clone(), value, Source::Arg)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{e:?}")))?;
+        if secret {
+            redact::add(prop.value());
+        }
+        prompted.push(prop.with_secret(secret));
+    }
+
+    let path: String = ask("Save values to an environment file (leave empty to skip): ", false)?;
+    if !path.is_empty() {
+        save(Path::new(&path), &prompted)?;
+    }
+
+    answers.extend(prompted.iter().cloned());
+    values.extend(prompted);
+    Ok(values)
+}
+
+fn ask(prompt: &str, hidden: bool) -> io::Result<String> {
+    let mut stderr = io::stderr();
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()?;
+
+    let echo = match hidden {
+        true => Echo::off(),
+        false => None,
+    };
+    let mut line = String::new();
+    let read: io::Result<usize> = io::stdin().lock().read_line(&mut line);
+    drop(echo);
+
+    match read? {
+        0 => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "No value was given")),
+        _ => Ok(line.trim_end_matches(['\r', '\n']).to_string()),
+    }
+}
+
+/// Append values to an environment file, which is created if it does not exist
+fn save(path: &Path, values: &[Property]) -> io::Result<()> {
+    if values.iter().any(Property::is_secret) && !path.to_string_lossy().ends_with(".sec") {
+        log::warn!("Secret values were saved to {:?}, which is not a .sec file", path);
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for value in values {
+        writeln!(file, "{}={}", value.key(), value.value())?;
+    }
+    eprintln!("Saved {} values to {}", values.len(), PathBuf::from(path).display());
+    Ok(())
+}
+
+/// Disables echo of input to the terminal while it exists
+struct Echo {
+    #[cfg(unix)]
+    original: libc::termios,
+}
+
+impl Echo {
+    #[cfg(unix)]
+    fn off() -> Option<Echo> {
+        // SAFETY: termios is a plain C struct that tcgetattr initializes before it is used
+        unsafe {
+            let mut original: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(libc::STDIN_FILENO, &mut original) != 0 {
+                return None;
+            }
+            let mut hidden: libc::termios = original;
+            hidden.c_lflag &= !libc::ECHO;
+            hidden.c_lflag |= libc::ECHONL;
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &hidden) != 0 {
+                return None;
+            }
+            Some(Echo { original })
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn off() -> Option<Echo> {
+        log::warn!("Input can not be hidden on this platform");
+        None
+    }
+}
+
+impl Drop for Echo {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        // SAFETY: restores the settings that were read by tcgetattr
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original);
+        }
+    }
+}
@@ -1,20 +1,39 @@
 use std::cmp::Ordering;
-use std::{fmt::Display, path::Path, str::FromStr};
+use std::fmt::{Debug, Display};
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::redact::{self, REDACTED};
+use crate::secrets::SecretsError;
+use crate::{dotenv, secrets};
+
+#[derive(Clone, PartialEq, Eq)]
 pub struct Property {
     key: String,
     value: String,
     source: Source,
+    secret: bool,
+    /// The file that the property was read from
+    origin: Option<PathBuf>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Source {
     EnvVar,
-    File(usize),
+    /// An environment file at a depth in the file system, which belongs to an environment given
+    /// with `--env`, or to no environment for the global `.env` and `.sec` files
+    File(usize, Option<Environment>),
+    Captured,
     Arg,
 }
 
+/// An environment given with `--env`, where `position` is its position among all given
+/// environments, starting from 1
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Environment {
+    pub position: usize,
+    pub name: String,
+}
+
 impl PartialOrd for Source {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         Some(self.cmp(other))
@@ -24,20 +43,35 @@ impl PartialOrd for Source {
 impl Ord for Source {
     fn cmp(&self, other: &Self) -> Ordering {
         match (self, other) {
-            (Source::EnvVar, Source::EnvVar) => Ordering::Equal,
-            (Source::Arg, Source::Arg) => Ordering::Equal,
-            (Source::File(d0), Source::File(d1)) => d1.cmp(d0),
-            (Source::File(_), Source::EnvVar) => Ordering::Less,
-            (Source::EnvVar, _) => Ordering::Greater,
-            (Source::File(_), Source::Arg) => Ordering::Greater,
-            (Source::Arg, _) => Ordering::Less,
+            // Deeper files take precedence, and at the same depth files of environments given later
+            // take precedence over earlier environments, which take precedence over global files
+            (Source::File(d0, e0), Source::File(d1, e1)) => d1.cmp(d0).then_with(|| e1.cmp(e0)),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl Source {
+    /// Rank of the source, where a lower rank takes precedence over a higher rank
+    fn rank(&self) -> u8 {
+        match self {
+            Source::Arg => 0,
+            Source::Captured => 1,
+            Source::File(..) => 2,
+            Source::EnvVar => 3,
         }
     }
 }
 
 impl Property {
     pub fn new(key: String, value: String, source: Source) -> Result<Property, ParsePropertyError> {
-        Ok(Property { key, value, source })
+        Ok(Property {
+            key,
+            value,
+            source,
+            secret: false,
+            origin: None,
+        })
     }
 
     pub fn key(&self) -> &str {
@@ -48,14 +82,55 @@ impl Property {
         &self.value
     }
 
+    /// Whether the value is a secret, because it was read from a `.sec` file
+    pub fn is_secret(&self) -> bool {
+        self.secret
+    }
+
     pub fn with_source(self, source: Source) -> Self {
         Property { source, ..self }
     }
+
+    pub fn with_secret(self, secret: bool) -> Self {
+        Property { secret, ..self }
+    }
+
+    pub fn source(&self) -> &Source {
+        &self.source
+    }
+
+    pub fn origin(&self) -> Option<&Path> {
+        self.origin.as_deref()
+    }
+
+    pub fn with_origin(self, origin: &Path) -> Self {
+        Property {
+            origin: Some(origin.to_path_buf()),
+            ..self
+        }
+    }
+}
+
+/// Values of secret properties are masked, so that they are never written to logs
+impl Debug for Property {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let value: &str = if self.secret { REDACTED } else { &self.value };
+        f.debug_struct("Property")
+            .field("key", &self.key)
+            .field("value", &value)
+            .field("source", &self.source)
+            .field("secret", &self.secret)
+            .field("origin", &self.origin)
+            .finish()
+    }
 }
 
 impl Ord for Property {
+    /// Properties are ordered by their source, and a secret property takes precedence over one from
+    /// the same source, so that a `.sec` file wins over the `.env` file of the same environment in
+    /// the same directory
     fn cmp(&self, other: &Self) -> Ordering {
-        self.source.cmp(&other.source)
+        self.source.cmp(&other.source).then_with(|| other.secret.cmp(&self.secret))
     }
 }
 
@@ -65,29 +140,45 @@ impl PartialOrd for Property {
     }
 }
 
-pub fn from_file(path: &Path) -> Result<Vec<Property>, ParsePropertyError> {
-    let content: String = std::fs::read_to_string(path)?;
-    let source: Source = source(path);
+pub fn from_file(
+    path: &Path,
+    environment: Option<Environment>,
+) -> Result<Vec<Property>, ParsePropertyError> {
+    let content: String = secrets::read(path, &secrets::identity_path(None))?;
+    let source: Source = source(path, environment);
+    let secret: bool = is_secret_file(path);
 
-    content
-        .lines()
+    let entries: Vec<(String, String)> = dotenv::parse(&content)
+        .map_err(|e| ParsePropertyError::Line(path.to_path_buf(), e.line, e.message))?;
+
+    entries
         .into_iter()
-        .map(Property::from_str)
-        .map(|prop| prop.map(|p| p.with_source(source)))
+        .map(|(key, value)| Property::new(key, value, source.clone()))
+        .map(|prop| prop.map(|p| p.with_secret(secret).with_origin(path)))
         .collect()
 }
 
-fn source(path: &Path) -> Source {
+/// Whether the file contains secrets, which is the case for the global `.sec` file and all
+/// `<environment>.sec` files, and the encrypted variants of them
+fn is_secret_file(path: &Path) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    secrets::plain_name(&name).ends_with(SECRET_EXTENSION)
+}
+
+fn source(path: &Path, environment: Option<Environment>) -> Source {
     let depth: usize = path.components().count();
-    Source::File(depth)
+    Source::File(depth, environment)
 }
 
 #[derive(Debug)]
 pub enum ParsePropertyError {
     Entry(String),
     Key(String),
-    Value(String),
     File(String),
+    /// An invalid entry in an environment file, on the given line
+    Line(PathBuf, usize, String),
+    /// An environment file that could not be decrypted
+    Decrypt(String),
 }
 
 impl From<std::io::Error> for ParsePropertyError {
@@ -96,13 +187,25 @@ impl From<std::io::Error> for ParsePropertyError {
     }
 }
 
+impl From<SecretsError> for ParsePropertyError {
+    fn from(e: SecretsError) -> Self {
+        match e {
+            SecretsError::File(_, err) => err.into(),
+            err => ParsePropertyError::Decrypt(err.to_string()),
+        }
+    }
+}
+
 impl Display for ParsePropertyError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             ParsePropertyError::Entry(entry) => write!(f, "Invalid entry: {}", entry),
             ParsePropertyError::Key(key) => write!(f, "Invalid key: {}", key),
-            ParsePropertyError::Value(value) => write!(f, "Invalid value: {}", value),
             ParsePropertyError::File(file) => write!(f, "Invalid value: {}", file),
+            ParsePropertyError::Line(path, line, msg) => {
+                write!(f, "Invalid entry in {:?} on line {}: {}", path, line, msg)
+            }
+            ParsePropertyError::Decrypt(msg) => f.write_str(msg),
         }
     }
 }
@@ -110,13 +213,20 @@ impl Display for ParsePropertyError {
 impl std::error::Error for ParsePropertyError {}
 
 const DELIMITER: char = '=';
+const SECRET_EXTENSION: &str = ".sec";
 
 impl std::str::FromStr for Property {
     type Err = ParsePropertyError;
 
+    /// A variable given as `KEY=value`, which is a secret if its name looks like one
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s.split_once(DELIMITER) {
-            Some((key, value)) => Property::new(normalize(key), normalize(value), Source::EnvVar),
+            Some((key, _)) if key.trim().is_empty() => Err(ParsePropertyError::Key(s.to_string())),
+            Some((key, value)) => {
+                let secret: bool = redact::is_secret_name(key);
+                Property::new(normalize(key), normalize(value), Source::EnvVar)
+                    .map(|prop| prop.with_secret(secret))
+            }
             None => Err(ParsePropertyError::Entry(s.to_string())),
         }
     }
@@ -130,8 +240,10 @@ fn normalize(input: &str) -> String {
 impl TryFrom<(String, String)> for Property {
     type Error = ParsePropertyError;
 
+    /// A system environment variable, which is a secret if its name looks like one
     fn try_from(value: (String, String)) -> Result<Self, Self::Error> {
-        Property::new(value.0, value.1, Source::EnvVar)
+        let secret: bool = redact::is_secret_name(&value.0);
+        Property::new(value.0, value.1, Source::EnvVar).map(|prop| prop.with_secret(secret))
     }
 }
 
@@ -139,24 +251,94 @@ impl TryFrom<(String, String)> for Property {
 mod tests {
     use crate::prop::Source;
 
-    use super::{ParsePropertyError, Property};
+    use super::{Environment, ParsePropertyError, Property};
 
     #[test]
     fn test_properties_sort_order() -> Result<(), ParsePropertyError> {
         let env_var = Property::new("key".to_string(), "env_var".to_string(), Source::EnvVar)?;
-        let file_root = Property::new("key".to_string(), "file_root".to_string(), Source::File(0))?;
+        let file_root =
+            Property::new("key".to_string(), "file_root".to_string(), Source::File(0, None))?;
         let file_child =
-            Property::new("key".to_string(), "file_child".to_string(), Source::File(1))?;
+            Property::new("key".to_string(), "file_child".to_string(), Source::File(1, None))?;
         let arg_var = Property::new("key".to_string(), "arg".to_string(), Source::Arg)?;
+        let captured = Property::new("key".to_string(), "captured".to_string(), Source::Captured)?;
 
-        let mut props: Vec<Property> = vec![file_root, file_child, arg_var, env_var];
+        let mut props: Vec<Property> = vec![file_root, captured, file_child, arg_var, env_var];
         props.sort();
 
         assert_eq!("arg", props[0].value());
-        assert_eq!("file_child", props[1].value());
-        assert_eq!("file_root", props[2].value());
-        assert_eq!("env_var", props[3].value());
+        assert_eq!("captured", props[1].value());
+        assert_eq!("file_child", props[2].value());
+        assert_eq!("file_root", props[3].value());
+        assert_eq!("env_var", props[4].value());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_later_environments_take_precedence() -> Result<(), ParsePropertyError> {
+        let env = |position: usize, name: &str| {
+            Some(Environment {
+                position,
+                name: name.to_string(),
+            })
+        };
+        let global = Property::new("key".to_string(), "global".to_string(), Source::File(3, None))?;
+        let first =
+            Property::new("key".to_string(), "first".to_string(), Source::File(3, env(1, "a")))?;
+        let second =
+            Property::new("key".to_string(), "second".to_string(), Source::File(3, env(2, "b")))?;
+        let deeper = Property::new("key".to_string(), "deeper".to_string(), Source::File(4, None))?;
+
+        let mut props: Vec<Property> = vec![first, global, deeper, second];
+        props.sort();
+
+        let values: Vec<&str> = props.iter().map(Property::value).collect();
+        assert_eq!(vec!["deeper", "second", "first", "global"], values);
 
         Ok(())
     }
+
+    #[test]
+    fn test_secret_files_take_precedence_over_env_files() -> Result<(), ParsePropertyError> {
+        let source = Source::File(
+            3,
+            Some(Environment {
+                position: 1,
+                name: "a".to_string(),
+            }),
+        );
+        let env = Property::new("key".to_string(), "env".to_string(), source.clone())?;
+        let sec = Property::new("key".to_string(), "sec".to_string(), source)?.with_secret(true);
+
+        for mut props in [vec![env.clone(), sec.clone()], vec![sec, env]] {
+            props.sort();
+            assert_eq!("sec", props[0].value());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_property_from_argument() {
+        let prop: Property = "key='value'".parse().unwrap();
+        assert_eq!(("key", "value"), (prop.key(), prop.value()));
+
+        assert!(matches!("=value".parse::<Property>(), Err(ParsePropertyError::Key(_))));
+        assert!(matches!("value".parse::<Property>(), Err(ParsePropertyError::Entry(_))));
+    }
+
+    #[test]
+    fn test_variables_with_secret_names_are_masked_in_logs() {
+        let props: Vec<Property> = vec![
+            "SERVICE_API_KEY=arg-s3cret".parse().unwrap(),
+            Property::try_from((String::from("GITHUB_TOKEN"), String::from("env-s3cret"))).unwrap(),
+            "USER=tom".parse().unwrap(),
+        ];
+
+        let logged: String = format!("Received properties {:?}", props);
+        assert!(!logged.contains("arg-s3cret"));
+        assert!(!logged.contains("env-s3cret"));
+        assert!(logged.contains("\"tom\""));
+    }
 }
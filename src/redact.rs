@@ -0,0 +1,171 @@
+//! Masking of secret values in everything that is written, like output, logs, reports and errors
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use regex::Regex;
+use url::Url;
+
+use crate::runner;
+
+pub const REDACTED: &str = "***";
+
+/// Names of headers which always have a secret value, in lower case
+const SENSITIVE_HEADERS: [&str; 6] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+];
+
+/// Shorter values, like `true` or `8080`, are too likely to occur by chance to be masked in other
+/// text
+const MIN_LENGTH: usize = 8;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+lazy_static! {
+    static ref SECRET_NAME: Regex =
+        Regex::new(r"(?i)token|secret|passw|pwd|api_?key|auth|credential|private").unwrap();
+    /// Secret values, ordered from the longest so that a value containing another is masked whole
+    static ref SECRETS: RwLock<Vec<String>> = RwLock::new(Vec::new());
+}
+
+/// Enable or disable masking, which is enabled by default
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Add a secret value, which is masked wherever it occurs from now on, including where it is
+/// encoded, like in a URL, a JSON string or an XML report
+pub fn add(value: &str) {
+    let value: &str = value.trim();
+    if value.chars().count() < MIN_LENGTH {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap();
+    for value in encodings(value) {
+        if !secrets.contains(&value) {
+            let position: usize = secrets.partition_point(|secret| secret.len() >= value.len());
+            secrets.insert(position, value);
+        }
+    }
+}
+
+/// A value as it is, and as it is written when encoded in a URL, a JSON string, a string formatted
+/// with `{:?}` like in TAP reports, and each of those escaped for XML like in JUnit reports
+fn encodings(value: &str) -> Vec<String> {
+    let quoted = |s: String| s[1..s.len() - 1].to_string();
+    let mut url: Url = Url::parse("http://localhost/").unwrap();
+    url.set_path(value);
+    url.set_query(Some(value));
+
+    let encoded: [String; 7] = [
+        value.to_string(),
+        url.path()[1..].to_string(),
+        url.query().unwrap_or_default().to_string(),
+        url::form_urlencoded::byte_serialize(value.as_bytes()).collect(),
+        utf8_percent_encode(value, NON_ALPHANUMERIC).to_string(),
+        quoted(serde_json::to_string(value).unwrap()),
+        quoted(format!("{value:?}")),
+    ];
+    encoded
+        .into_iter()
+        .flat_map(|encoded| [runner::escape_xml(&encoded), encoded])
+        .collect()
+}
+
+/// All secret values, or none if masking is disabled
+pub fn secrets() -> Vec<String> {
+    match is_enabled() {
+        true => SECRETS.read().unwrap().clone(),
+        false => Vec::new(),
+    }
+}
+
+/// Replace every secret value in `text` with `***`
+pub fn redact(text: &str) -> Cow<'_, str> {
+    if !is_enabled() {
+        return Cow::Borrowed(text);
+    }
+    let secrets = SECRETS.read().unwrap();
+    let mut text: Cow<str> = Cow::Borrowed(text);
+    for secret in secrets.iter() {
+        if text.contains(secret.as_str()) {
+            text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+        }
+    }
+    text
+}
+
+/// Whether the name of a variable looks like it holds a secret, such as `API_TOKEN`
+pub fn is_secret_name(name: &str) -> bool {
+    SECRET_NAME.is_match(name)
+}
+
+pub fn is_sensitive_header(name: &str) -> bool {
+    SENSITIVE_HEADERS.iter().any(|header| header.eq_ignore_ascii_case(name))
+}
+
+/// The value of a header to write, which is masked entirely for sensitive headers like
+/// `Authorization`, and otherwise has any secret values masked
+pub fn header<'a>(name: &str, value: &'a str) -> Cow<'a, str> {
+    match is_enabled() && is_sensitive_header(name) {
+        true => Cow::Borrowed(REDACTED),
+        false => redact(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add, header, redact, REDACTED};
+
+    #[test]
+    fn test_redact_secrets() {
+        add("s3cret-value");
+        add("s3cret-value-token");
+        add("prod");
+
+        assert_eq!("token=*** and ***", redact("token=s3cret-value-token and s3cret-value"));
+        assert_eq!("prod is too short", redact("prod is too short"));
+        assert_eq!("***", header("Authorization", "Bearer anything"));
+        assert_eq!("text/plain", header("Content-Type", "text/plain"));
+    }
+
+    #[test]
+    fn test_redact_encoded_secrets() {
+        let secret: &str = "s3cr et&\"val<ue>";
+        add(secret);
+
+        let mut url = url::Url::parse("http://localhost/x").unwrap();
+        url.query_pairs_mut().append_pair("token", secret);
+        let encoded: Vec<String> = vec![
+            url.to_string(),
+            serde_json::json!({ "token": secret }).to_string(),
+            format!("message: {:?}", format!("body is {secret}")),
+            format!(
+                "<failure>{}</failure>",
+                secret
+                    .replace('&', "&amp;")
+                    .replace('"', "&quot;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;")
+            ),
+        ];
+
+        for text in encoded {
+            let redacted = redact(&text);
+            assert!(redacted.contains(REDACTED), "{text}");
+            assert!(!redacted.contains("s3cr"), "{redacted}");
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::prop::{Property, Source};
+
+/// A variable used by a request, with all of its definitions in order of precedence, so the first
+/// definition is the one that is used
+#[derive(Debug)]
+pub struct Variable {
+    name: String,
+    definitions: Vec<Property>,
+}
+
+impl Variable {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The definition that is used, if the variable is defined at all
+    pub fn value(&self) -> Option<&Property> {
+        self.definitions.first()
+    }
+
+    /// Definitions that are overridden by the definition that is used
+    pub fn shadowed(&self) -> &[Property] {
+        self.definitions.get(1..).unwrap_or_default()
+    }
+}
+
+/// Resolve each of `names` among `props`, with the same precedence as when rendering templates
+pub fn resolve(names: &[String], props: &[Property]) -> Vec<Variable> {
+    let mut props: Vec<&Property> = props.iter().collect();
+    props.sort();
+
+    names
+        .iter()
+        .map(|name| Variable {
+            name: name.clone(),
+            definitions: props.iter().filter(|p| p.key() == name).map(|p| (*p).clone()).collect(),
+        })
+        .collect()
+}
+
+/// Properties from environment files or arguments that are not any of `names`
+pub fn unused<'a>(names: &[String], props: &'a [Property]) -> Vec<&'a Property> {
+    props
+        .iter()
+        .filter(|p| matches!(p.source(), Source::File(..) | Source::Arg))
+        .filter(|p| !names.iter().any(|name| name == p.key()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prop::{ParsePropertyError, Property, Source};
+
+    use super::{resolve, unused};
+
+    #[test]
+    fn test_resolve_variables() -> Result<(), ParsePropertyError> {
+        let prop = |key: &str, value: &str, source: Source| {
+            Property::new(key.to_string(), value.to_string(), source)
+        };
+        let props: Vec<Property> = vec![
+            prop("HOST", "env", Source::EnvVar)?,
+            prop("HOST", "root", Source::File(2, None))?,
+            prop("HOST", "child", Source::File(3, None))?,
+            prop("UNUSED", "value", Source::File(2, None))?,
+            prop("PATH", "/usr/bin", Source::EnvVar)?,
+        ];
+        let names: Vec<String> = vec![String::from("HOST"), String::from("TOKEN")];
+
+        let variables = resolve(&names, &props);
+        assert_eq!("child", variables[0].value().unwrap().value());
+        let shadowed: Vec<&str> = variables[0].shadowed().iter().map(Property::value).collect();
+        assert_eq!(vec!["root", "env"], shadowed);
+        assert_eq!("TOKEN", variables[1].name());
+        assert!(variables[1].value().is_none());
+
+        let unused: Vec<&str> = unused(&names, &props).iter().map(|p| p.key()).collect();
+        assert_eq!(vec!["UNUSED"], unused);
+
+        Ok(())
+    }
+}
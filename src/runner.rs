@@ -0,0 +1,340 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use walkdir::WalkDir;
+
+use crate::error::FireError;
+use crate::expect::Outcome;
+use crate::httpfile;
+use crate::redact;
+use crate::template;
+
+const EXTENSIONS: [&str; 2] = ["yml", "yaml"];
+const GLOB_CHARS: [char; 3] = ['*', '?', '['];
+
+/// The result of one request in a collection run
+#[derive(Debug)]
+pub struct TestCase {
+    file: PathBuf,
+    name: String,
+    duration: Duration,
+    result: CaseResult,
+}
+
+#[derive(Debug)]
+pub enum CaseResult {
+    Passed,
+    /// The request was executed, but one or several expectations failed
+    Failed(Vec<String>),
+    /// The request could not be executed, or no response was received
+    Error(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub errors: usize,
+}
+
+impl TestCase {
+    pub fn new(file: PathBuf, name: String, duration: Duration, result: CaseResult) -> TestCase {
+        TestCase {
+            file,
+            name,
+            duration,
+            result,
+        }
+    }
+
+    fn title(&self) -> String {
+        match self.name.as_str() {
+            "" => self.file.display().to_string(),
+            name => format!("{} {}", self.file.display(), name),
+        }
+    }
+}
+
+impl From<Vec<Outcome>> for CaseResult {
+    fn from(outcomes: Vec<Outcome>) -> Self {
+        let failures: Vec<String> = outcomes
+            .iter()
+            .filter_map(|o| o.failure().map(|f| format!("{} ({f})", o.description())))
+            .collect();
+
+        if failures.is_empty() {
+            CaseResult::Passed
+        } else {
+            CaseResult::Failed(failures)
+        }
+    }
+}
+
+impl From<FireError> for CaseResult {
+    fn from(err: FireError) -> Self {
+        CaseResult::Error(err.to_string())
+    }
+}
+
+impl Summary {
+    pub fn of<'a>(cases: impl IntoIterator<Item = &'a TestCase>) -> Summary {
+        cases.into_iter().fold(Summary::default(), |mut summary, case| {
+            summary.total += 1;
+            match case.result {
+                CaseResult::Passed => summary.passed += 1,
+                CaseResult::Failed(_) => summary.failed += 1,
+                CaseResult::Error(_) => summary.errors += 1,
+            }
+            summary
+        })
+    }
+
+    pub fn unsuccessful(&self) -> usize {
+        self.failed + self.errors
+    }
+}
+
+pub fn is_glob(path: &Path) -> bool {
+    path.to_string_lossy().contains(GLOB_CHARS)
+}
+
+/// The directory that contains all files matched by a glob pattern, which is the part of the
+/// pattern before the first component with a wildcard
+pub fn glob_base(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|c| !c.as_os_str().to_string_lossy().contains(GLOB_CHARS))
+        .collect()
+}
+
+/// All request files in a directory and its subdirectories, or all files matching a glob
+/// pattern, sorted by path. Hidden files and directories are skipped when searching a directory,
+/// and partials are always skipped.
+pub fn request_files(path: &Path) -> Result<Vec<PathBuf>, FireError> {
+    let base: PathBuf = if is_glob(path) { glob_base(path) } else { path.to_path_buf() };
+    if !base.as_os_str().is_empty() {
+        std::fs::metadata(&base).map_err(|e| FireError::io(e, &base))?;
+    }
+
+    let mut files: Vec<PathBuf> = if is_glob(path) {
+        let pattern: String = path.to_string_lossy().to_string();
+        glob::glob(&pattern)
+            .map_err(|e| FireError::Other(format!("Invalid pattern {pattern}: {e}")))?
+            .filter_map(|entry| entry.ok())
+            .filter(|path| path.is_file() && !is_partial(path))
+            .collect()
+    } else {
+        WalkDir::new(path)
+            .follow_links(false)
+            .into_iter()
+            .filter_entry(|e| e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.'))
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file() && !is_partial(entry.path()))
+            .map(|entry| entry.into_path())
+            .filter(|path| has_request_extension(path))
+            .collect()
+    };
+
+    files.sort();
+    Ok(files)
+}
+
+/// Whether the file is a partial in a `_partials` directory, rather than a request file
+fn is_partial(path: &Path) -> bool {
+    path.components().any(|c| c.as_os_str() == template::PARTIALS_DIR)
+}
+
+fn has_request_extension(path: &Path) -> bool {
+    match path.extension() {
+        Some(ext) => {
+            let ext = ext.to_string_lossy();
+            EXTENSIONS.contains(&ext.as_ref()) || httpfile::EXTENSIONS.contains(&ext.as_ref())
+        }
+        None => false,
+    }
+}
+
+/// Write a report, with any secret values masked
+pub fn write_report(path: &Path, content: &str) -> Result<(), FireError> {
+    std::fs::write(path, redact::redact(content).as_bytes()).map_err(|e| FireError::io(e, path))
+}
+
+/// Create a JUnit XML report, with one test suite per request file
+pub fn junit(cases: &[TestCase]) -> String {
+    let summary: Summary = Summary::of(cases);
+    let total_time: f64 = cases.iter().map(|c| c.duration.as_secs_f64()).sum();
+
+    let mut files: Vec<&Path> = cases.iter().map(|c| c.file.as_path()).collect();
+    files.dedup();
+
+    let mut xml: String = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"fire\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{:.3}\">\n",
+        summary.total, summary.failed, summary.errors, total_time
+    ));
+
+    for file in files {
+        let suite: Vec<&TestCase> = cases.iter().filter(|c| c.file == file).collect();
+        let summary: Summary = Summary::of(suite.iter().copied());
+        let time: f64 = suite.iter().map(|c| c.duration.as_secs_f64()).sum();
+        let name: String = escape_xml(&file.display().to_string());
+
+        xml.push_str(&format!(
+            "  <testsuite name=\"{name}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" time=\"{time:.3}\">\n",
+            summary.total, summary.failed, summary.errors
+        ));
+
+        for case in suite {
+            xml.push_str(&format!(
+                "    <testcase classname=\"{name}\" name=\"{}\" time=\"{:.3}\"",
+                escape_xml(&case.name),
+                case.duration.as_secs_f64()
+            ));
+            match &case.result {
+                CaseResult::Passed => xml.push_str("/>\n"),
+                CaseResult::Failed(failures) => {
+                    let message: String = format!("{} expectations failed", failures.len());
+                    xml.push_str(&format!(
+                        ">\n      <failure message=\"{message}\">{}</failure>\n    </testcase>\n",
+                        escape_xml(&failures.join("\n"))
+                    ));
+                }
+                CaseResult::Error(err) => {
+                    xml.push_str(&format!(
+                        ">\n      <error message=\"{}\"/>\n    </testcase>\n",
+                        escape_xml(err)
+                    ));
+                }
+            }
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Create a report in the Test Anything Protocol (TAP) format, version 13
+pub fn tap(cases: &[TestCase]) -> String {
+    let mut tap: String = format!("TAP version 13\n1..{}\n", cases.len());
+    for (i, case) in cases.iter().enumerate() {
+        let number: usize = i + 1;
+        let title: String = case.title();
+        match &case.result {
+            CaseResult::Passed => tap.push_str(&format!("ok {number} - {title}\n")),
+            CaseResult::Failed(failures) => {
+                tap.push_str(&format!("not ok {number} - {title}\n  ---\n  failures:\n"));
+                for failure in failures {
+                    tap.push_str(&format!("    - {:?}\n", failure));
+                }
+                tap.push_str("  ...\n");
+            }
+            CaseResult::Error(err) => {
+                tap.push_str(&format!(
+                    "not ok {number} - {title}\n  ---\n  message: {:?}\n  ...\n",
+                    err
+                ));
+            }
+        }
+    }
+    tap
+}
+
+pub fn escape_xml(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    use crate::redact;
+
+    use super::{junit, request_files, tap, write_report, CaseResult, TestCase};
+
+    fn cases() -> Vec<TestCase> {
+        let file = PathBuf::from("users.yml");
+        vec![
+            TestCase::new(
+                file.clone(),
+                "list".into(),
+                Duration::from_millis(12),
+                CaseResult::Passed,
+            ),
+            TestCase::new(
+                file,
+                "create".into(),
+                Duration::from_millis(30),
+                CaseResult::Failed(vec![String::from("status is 2xx (was 500)")]),
+            ),
+            TestCase::new(
+                PathBuf::from("auth.yml"),
+                String::new(),
+                Duration::ZERO,
+                CaseResult::Error(String::from("Request to <url> timed out")),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let xml: String = junit(&cases());
+
+        assert!(xml.contains("<testsuites name=\"fire\" tests=\"3\" failures=\"1\" errors=\"1\""));
+        assert!(
+            xml.contains("<testsuite name=\"users.yml\" tests=\"2\" failures=\"1\" errors=\"0\"")
+        );
+        assert!(xml.contains("<testcase classname=\"users.yml\" name=\"list\" time=\"0.012\"/>"));
+        assert!(xml.contains(
+            "<failure message=\"1 expectations failed\">status is 2xx (was 500)</failure>"
+        ));
+        assert!(xml.contains("<error message=\"Request to &lt;url&gt; timed out\"/>"));
+    }
+
+    #[test]
+    fn test_tap_report() {
+        let report: String = tap(&cases());
+        let lines: Vec<&str> = report.lines().collect();
+
+        assert_eq!("TAP version 13", lines[0]);
+        assert_eq!("1..3", lines[1]);
+        assert_eq!("ok 1 - users.yml list", lines[2]);
+        assert_eq!("not ok 2 - users.yml create", lines[3]);
+        assert!(report
+            .contains("not ok 3 - auth.yml\n  ---\n  message: \"Request to <url> timed out\""));
+    }
+
+    #[test]
+    fn test_missing_collection_is_an_error() {
+        let missing = PathBuf::from("missing-fire-collection");
+        assert!(request_files(&missing).is_err());
+        assert!(request_files(&missing.join("*.yml")).is_err());
+    }
+
+    #[test]
+    fn test_write_report_redacts_secrets() {
+        redact::add("report-s3cret-token");
+        let case = TestCase::new(
+            PathBuf::from("auth.yml"),
+            String::new(),
+            Duration::ZERO,
+            CaseResult::Failed(vec![String::from("body is \"report-s3cret-token\"")]),
+        );
+        let path: PathBuf =
+            std::env::temp_dir().join(format!("fire-report-{}.xml", std::process::id()));
+        write_report(&path, &junit(&[case])).unwrap();
+        let report: String = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!report.contains("report-s3cret-token"));
+        assert!(report.contains("body is &quot;***&quot;"));
+    }
+}
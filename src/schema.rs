@@ -0,0 +1,614 @@
+use std::fmt::Display;
+
+use regex::Regex;
+use serde_json::{Map, Value};
+
+/// Keywords which are not supported, and which are reported as violations rather than ignored, since
+/// ignoring them could let invalid values pass
+const UNSUPPORTED: [&str; 4] = [
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "$dynamicRef",
+    "$recursiveRef",
+];
+
+/// Validates values against a JSON Schema (draft 2020-12), where references (`$ref`) are resolved
+/// against a root document, which is either the schema itself or a document containing it, such as
+/// an OpenAPI document.
+///
+/// The keyword `nullable` of OpenAPI 3.0, and `exclusiveMinimum` and `exclusiveMaximum` as booleans,
+/// are supported as well. The keyword `format` is only an annotation and is not validated. References
+/// must be JSON pointers within the root document, like `#/$defs/tag`, so references to an `$id` or
+/// `$anchor` are reported as unresolved, and the keywords in [`UNSUPPORTED`] are reported as well.
+pub struct Validator<'a> {
+    root: &'a Value,
+}
+
+/// A reference followed at a location in the validated value, linked to the references followed
+/// before it. Following the same reference again at the same location would never end.
+struct Followed<'a> {
+    reference: &'a str,
+    pointer: &'a str,
+    previous: Option<&'a Followed<'a>>,
+}
+
+impl Followed<'_> {
+    fn contains(&self, reference: &str, pointer: &str) -> bool {
+        (self.reference == reference && self.pointer == pointer)
+            || self.previous.is_some_and(|previous| previous.contains(reference, pointer))
+    }
+}
+
+/// A value that does not conform to a schema, at a location given as a JSON pointer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub pointer: String,
+    pub keyword: String,
+    pub message: String,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(root: &'a Value) -> Validator<'a> {
+        Validator { root }
+    }
+
+    pub fn validate(&self, schema: &Value, instance: &Value) -> Vec<Violation> {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.check(schema, instance, "", None, &mut violations);
+        violations
+    }
+
+    fn is_valid(
+        &self,
+        schema: &Value,
+        instance: &Value,
+        pointer: &str,
+        followed: Option<&Followed>,
+    ) -> bool {
+        let mut violations: Vec<Violation> = Vec::new();
+        self.check(schema, instance, pointer, followed, &mut violations);
+        violations.is_empty()
+    }
+
+    fn check(
+        &self,
+        schema: &Value,
+        instance: &Value,
+        pointer: &str,
+        followed: Option<&Followed>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let schema: &Map<String, Value> = match schema {
+            Value::Object(schema) => schema,
+            Value::Bool(false) => {
+                return violations.push(violation(
+                    pointer,
+                    "false",
+                    String::from("no value is allowed"),
+                ))
+            }
+            _ => return,
+        };
+
+        for keyword in UNSUPPORTED.iter().filter(|keyword| schema.contains_key(**keyword)) {
+            violations.push(violation(pointer, keyword, String::from("keyword is not supported")));
+        }
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.root.pointer(reference.strip_prefix('#').unwrap_or("-")) {
+                Some(_) if followed.is_some_and(|f| f.contains(reference, pointer)) => {
+                    log::warn!(
+                        "Validation stopped at reference {reference}, which refers to itself"
+                    )
+                }
+                Some(target) => {
+                    let followed = Followed {
+                        reference,
+                        pointer,
+                        previous: followed,
+                    };
+                    self.check(target, instance, pointer, Some(&followed), violations)
+                }
+                None => violations.push(violation(
+                    pointer,
+                    "$ref",
+                    format!(
+                        "unable to resolve reference {reference}, only JSON pointers within the \
+                         document are supported"
+                    ),
+                )),
+            }
+        }
+
+        if instance.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+            return;
+        }
+
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                Value::String(kind) => vec![kind.as_str()],
+                _ => Vec::new(),
+            };
+            if !types.is_empty() && !types.iter().any(|kind| is_type(instance, kind)) {
+                violations.push(violation(
+                    pointer,
+                    "type",
+                    format!("expected {}, found {}", types.join(" or "), kind(instance)),
+                ));
+            }
+        }
+
+        if let Some(Value::Array(values)) = schema.get("enum") {
+            if !values.contains(instance) {
+                let values: Vec<String> = values.iter().map(Value::to_string).collect();
+                violations.push(violation(
+                    pointer,
+                    "enum",
+                    format!("{instance} is not one of {}", values.join(", ")),
+                ));
+            }
+        }
+
+        if let Some(value) = schema.get("const") {
+            if value != instance {
+                violations.push(violation(
+                    pointer,
+                    "const",
+                    format!("expected {value}, found {instance}"),
+                ));
+            }
+        }
+
+        match instance {
+            Value::Number(n) => {
+                let n: f64 = n.as_f64().unwrap_or_default();
+                let number = |key: &str| schema.get(key).and_then(Value::as_f64);
+                let exclusive = |key: &str| schema.get(key) == Some(&Value::Bool(true));
+                if let Some(m) = number("multipleOf").filter(|m| *m > 0.0) {
+                    let quotient: f64 = n / m;
+                    if (quotient - quotient.round()).abs() > 1e-9 {
+                        violations.push(violation(
+                            pointer,
+                            "multipleOf",
+                            format!("{n} is not a multiple of {m}"),
+                        ));
+                    }
+                }
+                if let Some(max) = number("maximum") {
+                    if n > max || (exclusive("exclusiveMaximum") && n == max) {
+                        violations.push(violation(
+                            pointer,
+                            "maximum",
+                            format!("{n} is greater than {max}"),
+                        ));
+                    }
+                }
+                if let Some(max) = number("exclusiveMaximum").filter(|max| n >= *max) {
+                    violations.push(violation(
+                        pointer,
+                        "exclusiveMaximum",
+                        format!("{n} is not less than {max}"),
+                    ));
+                }
+                if let Some(min) = number("minimum") {
+                    if n < min || (exclusive("exclusiveMinimum") && n == min) {
+                        violations.push(violation(
+                            pointer,
+                            "minimum",
+                            format!("{n} is less than {min}"),
+                        ));
+                    }
+                }
+                if let Some(min) = number("exclusiveMinimum").filter(|min| n <= *min) {
+                    violations.push(violation(
+                        pointer,
+                        "exclusiveMinimum",
+                        format!("{n} is not greater than {min}"),
+                    ));
+                }
+            }
+            Value::String(s) => {
+                let len: u64 = s.chars().count() as u64;
+                if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+                    if len > max {
+                        violations.push(violation(
+                            pointer,
+                            "maxLength",
+                            format!("length {len} is greater than {max}"),
+                        ));
+                    }
+                }
+                if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+                    if len < min {
+                        violations.push(violation(
+                            pointer,
+                            "minLength",
+                            format!("length {len} is less than {min}"),
+                        ));
+                    }
+                }
+                if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+                    match Regex::new(pattern) {
+                        Ok(regex) if !regex.is_match(s) => violations.push(violation(
+                            pointer,
+                            "pattern",
+                            format!("{instance} does not match {pattern}"),
+                        )),
+                        Ok(_) => {}
+                        Err(e) => log::warn!("Invalid pattern {pattern} in schema: {e}"),
+                    }
+                }
+            }
+            _ => {}
+        }
+
+        if let Value::Array(items) = instance {
+            self.check_array(schema, items, pointer, followed, violations);
+        }
+        if let Value::Object(object) = instance {
+            self.check_object(schema, object, pointer, followed, violations);
+        }
+
+        self.check_combinations(schema, instance, pointer, followed, violations);
+    }
+
+    fn check_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &[Value],
+        pointer: &str,
+        followed: Option<&Followed>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let len: u64 = items.len() as u64;
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64).filter(|max| len > *max) {
+            violations.push(violation(
+                pointer,
+                "maxItems",
+                format!("{len} items is more than {max}"),
+            ));
+        }
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64).filter(|min| len < *min) {
+            violations.push(violation(
+                pointer,
+                "minItems",
+                format!("{len} items is less than {min}"),
+            ));
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicate = items.iter().enumerate().find(|(i, item)| items[..*i].contains(item));
+            if let Some((_, item)) = duplicate {
+                violations.push(violation(
+                    pointer,
+                    "uniqueItems",
+                    format!("{item} occurs more than once"),
+                ));
+            }
+        }
+
+        if let Some(contains) = schema.get("contains") {
+            let count: u64 = items
+                .iter()
+                .enumerate()
+                .filter(|(i, item)| {
+                    self.is_valid(contains, item, &format!("{pointer}/{i}"), followed)
+                })
+                .count() as u64;
+            let min: u64 = schema.get("minContains").and_then(Value::as_u64).unwrap_or(1);
+            let max: Option<u64> = schema.get("maxContains").and_then(Value::as_u64);
+            if count < min {
+                violations.push(violation(
+                    pointer,
+                    "contains",
+                    format!("{count} items match, expected at least {min}"),
+                ));
+            }
+            if let Some(max) = max.filter(|max| count > *max) {
+                violations.push(violation(
+                    pointer,
+                    "maxContains",
+                    format!("{count} items match, expected at most {max}"),
+                ));
+            }
+        }
+
+        // An array of schemas for `items` is how earlier drafts express `prefixItems`
+        let (prefix, rest): (&[Value], Option<&Value>) =
+            match (schema.get("prefixItems"), schema.get("items")) {
+                (Some(Value::Array(prefix)), rest) => (prefix, rest),
+                (None, Some(Value::Array(prefix))) => (prefix, schema.get("additionalItems")),
+                (_, rest) => (&[], rest),
+            };
+        for (i, item) in items.iter().enumerate() {
+            let item_schema: Option<&Value> = prefix.get(i).or(rest);
+            if let Some(item_schema) = item_schema {
+                let pointer: String = format!("{pointer}/{i}");
+                self.check(item_schema, item, &pointer, followed, violations);
+            }
+        }
+    }
+
+    fn check_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &Map<String, Value>,
+        pointer: &str,
+        followed: Option<&Followed>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let len: u64 = object.len() as u64;
+        let limit = |key: &str| schema.get(key).and_then(Value::as_u64);
+        if let Some(max) = limit("maxProperties").filter(|max| len > *max) {
+            violations.push(violation(
+                pointer,
+                "maxProperties",
+                format!("{len} properties is more than {max}"),
+            ));
+        }
+        if let Some(min) = limit("minProperties").filter(|min| len < *min) {
+            violations.push(violation(
+                pointer,
+                "minProperties",
+                format!("{len} properties is less than {min}"),
+            ));
+        }
+
+        let required = schema.get("required").and_then(Value::as_array).into_iter().flatten();
+        for name in required.filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                violations.push(violation(
+                    pointer,
+                    "required",
+                    format!("property {name:?} is missing"),
+                ));
+            }
+        }
+
+        if let Some(Value::Object(dependencies)) = schema.get("dependentRequired") {
+            for (name, dependents) in dependencies.iter().filter(|(n, _)| object.contains_key(*n)) {
+                let dependents = dependents.as_array().into_iter().flatten();
+                for dependent in dependents.filter_map(Value::as_str) {
+                    if !object.contains_key(dependent) {
+                        let message = format!("property {dependent:?} is required by {name:?}");
+                        violations.push(violation(pointer, "dependentRequired", message));
+                    }
+                }
+            }
+        }
+
+        if let Some(Value::Object(dependencies)) = schema.get("dependentSchemas") {
+            let instance = Value::Object(object.clone());
+            for (_, dependent) in dependencies.iter().filter(|(n, _)| object.contains_key(*n)) {
+                self.check(dependent, &instance, pointer, followed, violations);
+            }
+        }
+
+        let properties: Option<&Map<String, Value>> =
+            schema.get("properties").and_then(Value::as_object);
+        let patterns: Vec<(Regex, &Value)> = schema
+            .get("patternProperties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .filter_map(|(pattern, schema)| Some((Regex::new(pattern).ok()?, schema)))
+            .collect();
+        let additional: Option<&Value> = schema.get("additionalProperties");
+        let names: Option<&Value> = schema.get("propertyNames");
+
+        for (name, value) in object {
+            let location: String = format!("{pointer}/{}", escape(name));
+            if let Some(names) = names {
+                let name = Value::String(name.clone());
+                self.check(names, &name, &location, followed, violations);
+            }
+
+            let mut evaluated: bool = false;
+            if let Some(property) = properties.and_then(|p| p.get(name)) {
+                self.check(property, value, &location, followed, violations);
+                evaluated = true;
+            }
+            for (_, property) in patterns.iter().filter(|(regex, _)| regex.is_match(name)) {
+                self.check(property, value, &location, followed, violations);
+                evaluated = true;
+            }
+            match additional {
+                Some(Value::Bool(false)) if !evaluated => violations.push(violation(
+                    &location,
+                    "additionalProperties",
+                    format!("property {name:?} is not allowed"),
+                )),
+                Some(additional) if !evaluated => {
+                    self.check(additional, value, &location, followed, violations)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn check_combinations(
+        &self,
+        schema: &Map<String, Value>,
+        instance: &Value,
+        pointer: &str,
+        followed: Option<&Followed>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let schemas = |key: &str| schema.get(key).and_then(Value::as_array);
+        if let Some(any) = schemas("anyOf") {
+            if !any.iter().any(|schema| self.is_valid(schema, instance, pointer, followed)) {
+                violations.push(violation(
+                    pointer,
+                    "anyOf",
+                    String::from("value does not match any of the schemas"),
+                ));
+            }
+        }
+        if let Some(one) = schemas("oneOf") {
+            let valid: usize =
+                one.iter().filter(|s| self.is_valid(s, instance, pointer, followed)).count();
+            if valid != 1 {
+                violations.push(violation(
+                    pointer,
+                    "oneOf",
+                    format!("value matches {valid} of the schemas, expected exactly 1"),
+                ));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.is_valid(not, instance, pointer, followed) {
+                violations.push(violation(
+                    pointer,
+                    "not",
+                    String::from("value matches a schema it must not match"),
+                ));
+            }
+        }
+
+        if let Some(condition) = schema.get("if") {
+            let branch: Option<&Value> = match self.is_valid(condition, instance, pointer, followed)
+            {
+                true => schema.get("then"),
+                false => schema.get("else"),
+            };
+            if let Some(branch) = branch {
+                self.check(branch, instance, pointer, followed, violations);
+            }
+        }
+        for all in schemas("allOf").into_iter().flatten() {
+            self.check(all, instance, pointer, followed, violations);
+        }
+    }
+}
+
+fn violation(pointer: &str, keyword: &str, message: String) -> Violation {
+    Violation {
+        pointer: pointer.to_string(),
+        keyword: keyword.to_string(),
+        message,
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let pointer: &str = if self.pointer.is_empty() { "(root)" } else { &self.pointer };
+        write!(f, "{pointer}: {} ({})", self.message, self.keyword)
+    }
+}
+
+fn is_type(instance: &Value, kind: &str) -> bool {
+    match kind {
+        "null" => instance.is_null(),
+        "boolean" => instance.is_boolean(),
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => match instance {
+            Value::Number(n) => {
+                n.is_i64() || n.is_u64() || n.as_f64().is_some_and(|n| n.fract() == 0.0)
+            }
+            _ => false,
+        },
+        _ => true,
+    }
+}
+
+fn kind(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_i64() || n.is_u64() => "integer",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Escape a property name for use in a JSON pointer
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{Validator, Violation};
+
+    #[test]
+    fn test_validate_json_schema() {
+        let schema = json!({
+            "$defs": {
+                "tag": { "type": "string", "minLength": 2 }
+            },
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": { "type": "integer", "minimum": 1 },
+                "name": { "type": "string" },
+                "email": { "type": ["string", "null"], "pattern": "@" },
+                "tags": { "type": "array", "items": { "$ref": "#/$defs/tag" }, "uniqueItems": true },
+                "owner": { "$ref": "#" }
+            },
+            "additionalProperties": false
+        });
+        let instance = json!({
+            "id": 0,
+            "email": null,
+            "tags": ["ok", "x", "ok"],
+            "owner": { "id": 2, "name": "tom", "a/b": 1 },
+            "color": "red"
+        });
+
+        let violations: Vec<String> = Validator::new(&schema)
+            .validate(&schema, &instance)
+            .iter()
+            .map(Violation::to_string)
+            .collect();
+
+        assert_eq!(
+            vec![
+                "(root): property \"name\" is missing (required)",
+                "/color: property \"color\" is not allowed (additionalProperties)",
+                "/id: 0 is less than 1 (minimum)",
+                "/owner/a~1b: property \"a/b\" is not allowed (additionalProperties)",
+                "/tags: \"ok\" occurs more than once (uniqueItems)",
+                "/tags/1: length 1 is less than 2 (minLength)",
+            ],
+            violations
+        );
+    }
+
+    #[test]
+    fn test_validate_recursive_and_unsupported_schemas() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "child": { "$ref": "#" }
+            }
+        });
+        let mut instance = json!({ "name": 1 });
+        for _ in 0..100 {
+            instance = json!({ "name": "node", "child": instance });
+        }
+        let violations: Vec<Violation> = Validator::new(&schema).validate(&schema, &instance);
+        assert_eq!(1, violations.len());
+        assert_eq!("type", violations[0].keyword);
+        assert_eq!(100, violations[0].pointer.matches("/child").count());
+
+        let looping = json!({ "$defs": { "a": { "$ref": "#/$defs/a" } }, "$ref": "#/$defs/a" });
+        assert!(Validator::new(&looping).validate(&looping, &json!(1)).is_empty());
+
+        let unsupported = json!({ "unevaluatedProperties": false });
+        let violations: Vec<Violation> =
+            Validator::new(&unsupported).validate(&unsupported, &json!({ "a": 1 }));
+        assert_eq!("unevaluatedProperties", violations[0].keyword);
+
+        let anchored = json!({ "$defs": { "a": { "$anchor": "a" } }, "$ref": "#a" });
+        let violations: Vec<Violation> = Validator::new(&anchored).validate(&anchored, &json!(1));
+        assert_eq!("$ref", violations[0].keyword);
+    }
+}
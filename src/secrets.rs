@@ -0,0 +1,397 @@
+//! Encrypted environment files, like `staging.sec.age` or `staging.sec.gpg`, which are decrypted
+//! with a local identity so that they can be committed
+
+use std::fmt::Display;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::age::{self, AgeError, Identity, Recipient};
+use crate::dotenv;
+
+const AGE_EXTENSION: &str = ".age";
+const GPG_EXTENSION: &str = ".gpg";
+const IDENTITY_VAR: &str = "FIRE_IDENTITY";
+/// A file with the public keys of everyone that can decrypt the encrypted files in its directory
+/// and its subdirectories
+const RECIPIENTS_FILE: &str = ".recipients";
+
+#[derive(Debug)]
+pub enum SecretsError {
+    File(PathBuf, std::io::Error),
+    Exists(PathBuf),
+    /// No identity file at the path, to decrypt with
+    NoIdentity(PathBuf),
+    Age(PathBuf, AgeError),
+    Gpg(PathBuf, String),
+    /// A file encrypted with GPG, which can only be decrypted
+    GpgReadOnly(PathBuf),
+    Editor(String),
+    /// Edited content which is not a valid environment file
+    Invalid(dotenv::Error),
+}
+
+impl Display for SecretsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretsError::File(path, err) => write!(f, "Unable to use {:?}: {err}", path),
+            SecretsError::Exists(path) => {
+                write!(f, "File {:?} already exists, use --force to overwrite it", path)
+            }
+            SecretsError::NoIdentity(path) => write!(
+                f,
+                "No identity in {:?}, create one with `fire secrets keygen` or set {IDENTITY_VAR}",
+                path
+            ),
+            SecretsError::Age(path, err) => write!(f, "{:?}: {err}", path),
+            SecretsError::Gpg(path, err) => {
+                write!(f, "Unable to decrypt {:?} with gpg: {err}", path)
+            }
+            SecretsError::GpgReadOnly(path) => write!(
+                f,
+                "{:?} is encrypted with GPG, which fire can only decrypt, so edit it with gpg instead",
+                path
+            ),
+            SecretsError::Editor(err) => write!(f, "Editor failed: {err}"),
+            SecretsError::Invalid(err) => {
+                write!(f, "Changes were discarded, since they are invalid on {err}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretsError {}
+
+/// The name of a file without the extension of an encrypted file, so `staging.sec.age` is
+/// `staging.sec`
+pub fn plain_name(name: &str) -> &str {
+    name.strip_suffix(AGE_EXTENSION)
+        .or_else(|| name.strip_suffix(GPG_EXTENSION))
+        .unwrap_or(name)
+}
+
+/// Names of the encrypted variants of a file name
+pub fn encrypted_names(name: &str) -> [String; 2] {
+    [
+        name.to_string() + AGE_EXTENSION,
+        name.to_string() + GPG_EXTENSION,
+    ]
+}
+
+/// Read an environment file, which is decrypted with the identity file `identity` if it is
+/// encrypted with age, or with `gpg` if it is encrypted with GPG
+pub fn read(path: &Path, identity: &Path) -> Result<String, SecretsError> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with(AGE_EXTENSION) {
+        let identities: Vec<Identity> = identities(identity)?;
+        let file: Vec<u8> = read_file(path)?;
+        let content: Vec<u8> = age::decrypt(&file, &identities)
+            .map_err(|e| SecretsError::Age(path.to_path_buf(), e))?;
+        utf8(path, content)
+    } else if name.ends_with(GPG_EXTENSION) {
+        gpg_decrypt(path)
+    } else {
+        std::fs::read_to_string(path).map_err(|e| SecretsError::File(path.to_path_buf(), e))
+    }
+}
+
+/// The identity file, which is `identity` if given, or otherwise the file in the environment
+/// variable `FIRE_IDENTITY`, or `~/.config/fire/identity.txt`
+pub fn identity_path(identity: Option<&Path>) -> PathBuf {
+    if let Some(identity) = identity {
+        return identity.to_path_buf();
+    }
+    match std::env::var_os(IDENTITY_VAR) {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+            let config: PathBuf = match std::env::var_os("XDG_CONFIG_HOME") {
+                Some(config) => PathBuf::from(config),
+                None => PathBuf::from(home.unwrap_or_default()).join(".config"),
+            };
+            config.join("fire").join("identity.txt")
+        }
+    }
+}
+
+fn identities(path: &Path) -> Result<Vec<Identity>, SecretsError> {
+    let content: String = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Err(SecretsError::NoIdentity(path.to_path_buf()))
+        }
+        Err(e) => return Err(SecretsError::File(path.to_path_buf(), e)),
+    };
+    let identities: Vec<Identity> =
+        Identity::parse_file(&content).map_err(|e| SecretsError::Age(path.to_path_buf(), e))?;
+    match identities.is_empty() {
+        true => Err(SecretsError::NoIdentity(path.to_path_buf())),
+        false => Ok(identities),
+    }
+}
+
+/// The recipients to encrypt `file` to, which are the `keys` and the keys in `files` if any are
+/// given. Otherwise they are the keys in the closest `.recipients` file in the directory of `file`
+/// or a parent directory, or else the recipient of the identity.
+pub fn recipients(
+    file: &Path,
+    keys: &[String],
+    files: &[PathBuf],
+    identity: &Path,
+) -> Result<Vec<Recipient>, SecretsError> {
+    let mut keys: Vec<String> = keys.to_vec();
+    for path in files {
+        keys.extend(read_keys(path)?);
+    }
+
+    if keys.is_empty() {
+        let dir: PathBuf = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        let closest: Option<PathBuf> =
+            dir.ancestors().skip(1).map(|d| d.join(RECIPIENTS_FILE)).find(|f| f.is_file());
+        match closest {
+            Some(path) => keys.extend(read_keys(&path)?),
+            None => {
+                let own = identities(identity)?.into_iter().map(|i| i.recipient());
+                return own
+                    .collect::<Result<_, _>>()
+                    .map_err(|e| SecretsError::Age(identity.to_path_buf(), e));
+            }
+        }
+    }
+
+    keys.iter()
+        .map(|key| Recipient::parse(key).map_err(|e| SecretsError::Age(file.to_path_buf(), e)))
+        .collect()
+}
+
+/// Public keys in a file, one per line, ignoring empty lines and `#` comments
+fn read_keys(path: &Path) -> Result<Vec<String>, SecretsError> {
+    let content: String =
+        std::fs::read_to_string(path).map_err(|e| SecretsError::File(path.to_path_buf(), e))?;
+    let keys = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+    Ok(keys.map(String::from).collect())
+}
+
+/// Encrypt `file` to a file with the same name ending with `.age`, which is returned
+pub fn encrypt(
+    file: &Path,
+    recipients: &[Recipient],
+    force: bool,
+) -> Result<PathBuf, SecretsError> {
+    let output: PathBuf = encrypted_path(file)?;
+    if output.exists() && !force {
+        return Err(SecretsError::Exists(output));
+    }
+    let content: Vec<u8> = read_file(file)?;
+    write_encrypted(&output, &content, recipients)?;
+    Ok(output)
+}
+
+/// Decrypt `file`, or start from an empty file if it does not exist, open it in the editor of the
+/// user and encrypt the edited content to `recipients`. Returns whether the content was changed.
+pub fn edit(file: &Path, identity: &Path, recipients: &[Recipient]) -> Result<bool, SecretsError> {
+    let file: PathBuf = encrypted_path(file)?;
+    let content: Vec<u8> = match file.exists() {
+        true => age::decrypt(&read_file(&file)?, &identities(identity)?)
+            .map_err(|e| SecretsError::Age(file.clone(), e))?,
+        false => Vec::new(),
+    };
+
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    let temp = TempFile::create(plain_name(&name), &content)?;
+    open_editor(&temp.file)?;
+    let edited: Vec<u8> = read_file(&temp.file)?;
+    drop(temp);
+
+    if edited == content {
+        return Ok(false);
+    }
+    dotenv::parse(&utf8(&file, edited.clone())?).map_err(SecretsError::Invalid)?;
+    write_encrypted(&file, &edited, recipients)?;
+    Ok(true)
+}
+
+/// Create a new identity in the identity file, and return its recipient
+pub fn keygen(identity: &Path, force: bool) -> Result<Recipient, SecretsError> {
+    if identity.exists() && !force {
+        return Err(SecretsError::Exists(identity.to_path_buf()));
+    }
+    let age = |e: AgeError| SecretsError::Age(identity.to_path_buf(), e);
+    let key: Identity = Identity::generate().map_err(age)?;
+    let recipient: Recipient = key.recipient().map_err(age)?;
+
+    if let Some(dir) = identity.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| SecretsError::File(dir.to_path_buf(), e))?;
+    }
+    let content = format!("# public key: {recipient}\n{key}\n");
+    let name = identity.file_name().unwrap_or_default().to_string_lossy();
+    let temp: PathBuf = identity.with_file_name(format!(".{name}.{}", random_name()?));
+    write_private(&temp, content.as_bytes())?;
+    std::fs::rename(&temp, identity).map_err(|e| {
+        let _ = std::fs::remove_file(&temp);
+        SecretsError::File(identity.to_path_buf(), e)
+    })?;
+    Ok(recipient)
+}
+
+/// The file encrypted with age for `file`, which is not allowed to be encrypted with GPG
+fn encrypted_path(file: &Path) -> Result<PathBuf, SecretsError> {
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    if name.ends_with(GPG_EXTENSION) {
+        Err(SecretsError::GpgReadOnly(file.to_path_buf()))
+    } else if name.ends_with(AGE_EXTENSION) {
+        Ok(file.to_path_buf())
+    } else {
+        Ok(file.with_file_name(format!("{name}{AGE_EXTENSION}")))
+    }
+}
+
+fn write_encrypted(
+    path: &Path,
+    content: &[u8],
+    recipients: &[Recipient],
+) -> Result<(), SecretsError> {
+    let encrypted: Vec<u8> =
+        age::encrypt(content, recipients).map_err(|e| SecretsError::Age(path.to_path_buf(), e))?;
+    std::fs::write(path, encrypted).map_err(|e| SecretsError::File(path.to_path_buf(), e))
+}
+
+fn gpg_decrypt(path: &Path) -> Result<String, SecretsError> {
+    let output = Command::new("gpg")
+        .args(["--quiet", "--batch", "--decrypt"])
+        .arg(path)
+        .output()
+        .map_err(|e| SecretsError::Gpg(path.to_path_buf(), e.to_string()))?;
+    if !output.status.success() {
+        let err: String = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(SecretsError::Gpg(path.to_path_buf(), err));
+    }
+    utf8(path, output.stdout)
+}
+
+/// Open a file in the editor in `VISUAL` or `EDITOR`, and wait for it to be closed
+fn open_editor(path: &Path) -> Result<(), SecretsError> {
+    let default: &str = if cfg!(windows) { "notepad" } else { "vi" };
+    let editor: String = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| default.to_string());
+    let mut parts = editor.split_whitespace();
+    let program: &str = parts.next().unwrap_or(default);
+
+    let status = Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| SecretsError::Editor(format!("{program}: {e}")))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(SecretsError::Editor(format!("{program} exited with {status}"))),
+    }
+}
+
+fn read_file(path: &Path) -> Result<Vec<u8>, SecretsError> {
+    std::fs::read(path).map_err(|e| SecretsError::File(path.to_path_buf(), e))
+}
+
+fn utf8(path: &Path, content: Vec<u8>) -> Result<String, SecretsError> {
+    String::from_utf8(content).map_err(|_| {
+        let err =
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "Content is not valid UTF-8");
+        SecretsError::File(path.to_path_buf(), err)
+    })
+}
+
+/// Write a new file that only the user can access, failing if anything already exists at `path`
+fn write_private(path: &Path, content: &[u8]) -> Result<(), SecretsError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    let err = |e| SecretsError::File(path.to_path_buf(), e);
+    let mut file = options.open(path).map_err(err)?;
+    file.write_all(content).map_err(err)
+}
+
+fn random_name() -> Result<String, SecretsError> {
+    let mut bytes = [0u8; 16];
+    openssl::rand::rand_bytes(&mut bytes)
+        .map_err(|e| SecretsError::Age(PathBuf::new(), AgeError::Crypto(e)))?;
+    Ok(bytes.iter().map(|b| format!("{b:02x}")).collect())
+}
+
+/// A file with decrypted content in a new directory with a random name in the temporary directory,
+/// which only the user can access, and which is removed when dropped
+struct TempFile {
+    dir: PathBuf,
+    file: PathBuf,
+}
+
+impl TempFile {
+    fn create(name: &str, content: &[u8]) -> Result<TempFile, SecretsError> {
+        let dir: PathBuf = std::env::temp_dir().join(format!("fire-{}", random_name()?));
+        let mut builder = std::fs::DirBuilder::new();
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(&dir).map_err(|e| SecretsError::File(dir.clone(), e))?;
+
+        let temp = TempFile {
+            file: dir.join(name),
+            dir,
+        };
+        write_private(&temp.file, content)?;
+        Ok(temp)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.dir) {
+            log::warn!("Unable to remove decrypted file {:?}: {e}", self.file);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::{edit, encrypt, keygen, plain_name, read, recipients};
+
+    #[test]
+    fn test_read_encrypted_file() {
+        assert_eq!("staging.sec", plain_name("staging.sec.age"));
+        assert_eq!("staging.sec", plain_name("staging.sec.gpg"));
+        assert_eq!(".sec", plain_name(".sec"));
+
+        let dir: PathBuf =
+            std::env::temp_dir().join(format!("fire-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let identity: PathBuf = dir.join("identity.txt");
+        keygen(&identity, false).unwrap();
+        assert!(keygen(&identity, false).is_err());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&identity, std::fs::Permissions::from_mode(0o644)).unwrap();
+            keygen(&identity, true).unwrap();
+            let mode: u32 = std::fs::metadata(&identity).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+
+        let file: PathBuf = dir.join("staging.sec");
+        std::fs::write(&file, "TOKEN=secret\n").unwrap();
+        let recipients = recipients(&file, &[], &[], &identity).unwrap();
+        let encrypted: PathBuf = encrypt(&file, &recipients, false).unwrap();
+        assert_eq!(dir.join("staging.sec.age"), encrypted);
+        assert!(!std::fs::read_to_string(&encrypted).unwrap_or_default().contains("secret"));
+
+        assert_eq!("TOKEN=secret\n", read(Path::new(&encrypted), &identity).unwrap());
+        assert!(edit(&dir.join("staging.sec.gpg"), &identity, &recipients).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
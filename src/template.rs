@@ -1,35 +1,522 @@
-use handlebars::{no_escape, Handlebars};
-use std::collections::HashMap;
+use handlebars::template::{HelperTemplate, Parameter, TemplateElement};
+use handlebars::{no_escape, Handlebars, RenderError, Template};
+use serde_json::Map;
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
 
+use crate::helpers::{self, ExternalError};
 use crate::prop::Property;
+use crate::redact::REDACTED;
 
-pub fn substitution(input: String, vars: Vec<Property>) -> Result<String, SubstitutionError> {
-    let vars: HashMap<String, String> = merge(vars);
+/// Keys of a request whose content is not substituted as text, but parsed first and then rendered
+/// one string value at a time, so substituted values can never break the structure of the document.
+/// Only keys at the top level of the request are lifted, so that for example `json` under `expect`
+/// or `capture` is substituted as text like the rest of the document.
+const LEAF_TEMPLATED_KEYS: [&str; 1] = ["json"];
+/// Helpers that are built into Handlebars, which are not mistaken for variables
+const BUILTIN_HELPERS: [&str; 17] = [
+    "if", "unless", "each", "with", "lookup", "log", "raw", "eq", "ne", "gt", "gte", "lt", "lte",
+    "and", "or", "not", "len",
+];
+
+/// Name of directories with partials, which can be included in any template with `{{> name}}`
+pub const PARTIALS_DIR: &str = "_partials";
+
+/// Options for rendering templates, which are the same for all templates in a run
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    partials: HashMap<String, String>,
+    external: bool,
+}
+
+impl Options {
+    /// Allow templates to run commands with `exec` and to read files with `file`
+    pub fn with_external(self, external: bool) -> Self {
+        Options { external, ..self }
+    }
+
+    /// Read all files in `dirs` as partials, named by their path relative to the directory without
+    /// extension, like `auth/bearer` for `_partials/auth/bearer.yml`. A partial in a later
+    /// directory takes precedence over a partial with the same name in an earlier directory.
+    pub fn with_partials(mut self, dirs: &[PathBuf]) -> Result<Self, SubstitutionError> {
+        for dir in dirs {
+            let files = WalkDir::new(dir)
+                .follow_links(true)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_entry(|e| {
+                    e.depth() == 0 || !e.file_name().to_string_lossy().starts_with('.')
+                })
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.file_type().is_file());
+
+            for file in files {
+                let content: String = std::fs::read_to_string(file.path()).map_err(|e| {
+                    SubstitutionError::InvalidPartial(format!("{:?}: {e}", file.path()))
+                })?;
+                let name: String = partial_name(file.path().strip_prefix(dir).unwrap());
+                log::debug!("Found partial {name} in {:?}", file.path());
+                self.partials.insert(name, content);
+            }
+        }
+        Ok(self)
+    }
+}
+
+fn partial_name(path: &Path) -> String {
+    let path: PathBuf = path.with_extension("");
+    let parts: Vec<String> =
+        path.components().map(|c| c.as_os_str().to_string_lossy().to_string()).collect();
+    parts.join("/")
+}
+
+pub fn substitution(
+    input: String,
+    vars: Vec<Property>,
+    options: &Options,
+) -> Result<String, SubstitutionError> {
+    let vars: serde_json::Value = context(merge(vars));
+    let reg: Handlebars = registry(options)?;
+    render_document(&reg, &input, &vars).map_err(|e| match e {
+        SubstitutionError::MissingValue(_) => {
+            let names: Vec<String> = unresolved(&reg, &input, &vars);
+            if names.is_empty() {
+                e
+            } else {
+                SubstitutionError::Unresolved(names)
+            }
+        }
+        e => e,
+    })
+}
+
+fn render_document(
+    reg: &Handlebars,
+    input: &str,
+    vars: &serde_json::Value,
+) -> Result<String, SubstitutionError> {
+    let (input, sections) = extract_sections(input);
+    let mut output: String = render(reg, &input, vars)?;
+
+    for section in sections {
+        let rendered: String = section.render(reg, vars)?;
+        output = output.replacen(&section.placeholder, &rendered, 1);
+    }
+
+    Ok(output)
+}
+
+#[derive(Debug)]
+pub enum SubstitutionError {
+    MissingValue(String),
+    /// Variables that are used in the template, but have no value
+    Unresolved(Vec<String>),
+    InvalidSection(String),
+    InvalidPartial(String),
+    /// A command run by `exec` failed, or a file read by `file` could not be read
+    External(String),
+}
+
+fn registry(options: &Options) -> Result<Handlebars<'static>, SubstitutionError> {
     let mut reg = Handlebars::new();
     reg.register_escape_fn(no_escape);
     reg.set_strict_mode(true);
-    reg.register_template_string("template", input).unwrap();
-    match reg.render("template", &vars) {
+    helpers::register(&mut reg);
+    helpers::register_external(&mut reg, options.external);
+    for (name, template) in &options.partials {
+        reg.register_partial(name, template)
+            .map_err(|e| SubstitutionError::InvalidPartial(format!("{name}: {e}")))?;
+    }
+    Ok(reg)
+}
+
+fn render(
+    reg: &Handlebars,
+    template: &str,
+    vars: &serde_json::Value,
+) -> Result<String, SubstitutionError> {
+    match reg.render_template(template, vars) {
         Ok(output) => Ok(output),
+        Err(e) if external_error(&e).is_some() => Err(SubstitutionError::External(e.desc)),
         Err(e) => Err(SubstitutionError::MissingValue(e.desc)),
     }
 }
 
-#[derive(Debug)]
-pub enum SubstitutionError {
-    MissingValue(String),
+fn external_error(err: &RenderError) -> Option<&ExternalError> {
+    std::error::Error::source(err).and_then(|source| source.downcast_ref())
+}
+
+fn render_leaves(
+    reg: &Handlebars,
+    value: Value,
+    vars: &serde_json::Value,
+) -> Result<Value, SubstitutionError> {
+    match value {
+        Value::String(s) => render(reg, &s, vars).map(Value::String),
+        Value::Sequence(seq) => seq
+            .into_iter()
+            .map(|v| render_leaves(reg, v, vars))
+            .collect::<Result<_, _>>()
+            .map(Value::Sequence),
+        Value::Mapping(map) => {
+            let mut rendered = Mapping::with_capacity(map.len());
+            for (k, v) in map {
+                rendered.insert(k, render_leaves(reg, v, vars)?);
+            }
+            Ok(Value::Mapping(rendered))
+        }
+        other => Ok(other),
+    }
+}
+
+/// A section of a request file belonging to one of the [`LEAF_TEMPLATED_KEYS`], lifted out of the
+/// document before the textual substitution and put back once its values have been rendered. The
+/// placeholder contains a random nonce, so it can not collide with any content of the document or
+/// values substituted into it.
+struct Section {
+    key: &'static str,
+    indent: usize,
+    yaml: String,
+    placeholder: String,
+}
+
+impl Section {
+    fn render(
+        &self,
+        reg: &Handlebars,
+        vars: &serde_json::Value,
+    ) -> Result<String, SubstitutionError> {
+        let mut mapping: Mapping = serde_yaml::from_str(&self.yaml)
+            .map_err(|e| SubstitutionError::InvalidSection(format!("{}: {e}", self.key)))?;
+        let value: Value = mapping.remove(self.key).unwrap_or_default();
+        let value: Value = render_leaves(reg, value, vars)?;
+        let yaml: String = serde_yaml::to_string(&value).unwrap();
+
+        let indent: String = " ".repeat(self.indent);
+        let body: String = yaml
+            .lines()
+            .map(|line| format!("{indent}  {line}"))
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Ok(format!("{indent}{}:\n{body}", self.key))
+    }
+}
+
+fn extract_sections(input: &str) -> (String, Vec<Section>) {
+    let nonce: String = nonce();
+    let lines: Vec<&str> = input.lines().collect();
+    let mut output: Vec<String> = Vec::with_capacity(lines.len());
+    let mut sections: Vec<Section> = Vec::new();
+    let mut block_scalar: Option<usize> = None;
+    let mut i: usize = 0;
+    // The indentation of the keys of the request, which is the indentation of its first key
+    let top_level: Option<usize> = lines
+        .iter()
+        .find(|line| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|line| indentation(line));
+
+    while i < lines.len() {
+        let line: &str = lines[i];
+        let indent: usize = indentation(line);
+        i += 1;
+
+        if let Some(owner) = block_scalar {
+            if line.trim().is_empty() || indent > owner {
+                output.push(line.to_string());
+                continue;
+            }
+            block_scalar = None;
+        }
+
+        let trimmed: &str = &line[indent..];
+        let key: Option<(&'static str, &str)> =
+            section_key(trimmed).filter(|_| top_level == Some(indent));
+        match key {
+            Some((key, rest)) => {
+                let mut yaml: Vec<String> = vec![format!("{key}:{rest}")];
+                let mut depth: isize = flow_depth(rest);
+                while i < lines.len() && (depth > 0 || in_section(lines[i], indent)) {
+                    depth += flow_depth(lines[i]);
+                    yaml.push(lines[i].get(indent..).unwrap_or(lines[i].trim_start()).to_string());
+                    i += 1;
+                }
+                let placeholder: String = format!(
+                    "{}{key}: __FIRE_SECTION_{nonce}_{}__",
+                    " ".repeat(indent),
+                    sections.len()
+                );
+                output.push(placeholder.clone());
+                sections.push(Section {
+                    key,
+                    indent,
+                    yaml: yaml.join("\n"),
+                    placeholder,
+                });
+            }
+            None => {
+                if starts_block_scalar(trimmed) {
+                    block_scalar = Some(indent);
+                }
+                output.push(line.to_string());
+            }
+        }
+    }
+
+    let mut output: String = output.join("\n");
+    if input.ends_with('\n') {
+        output.push('\n');
+    }
+
+    (output, sections)
+}
+
+/// All variables used in a template, including those in partials it includes, in the order they are
+/// first used
+pub fn variables(template: &str, options: &Options) -> Result<Vec<String>, SubstitutionError> {
+    let reg: Handlebars = registry(options)?;
+    let (template, sections) = extract_sections(template);
+    let templates = std::iter::once(template).chain(sections.iter().map(|s| s.yaml.clone()));
+
+    let mut names: Vec<String> = Vec::new();
+    for template in templates {
+        let template: Template = Template::compile(&template)
+            .map_err(|e| SubstitutionError::MissingValue(e.to_string()))?;
+        referenced(&reg, &template.elements, &mut names, &mut Vec::new());
+    }
+    let mut unique: Vec<String> = Vec::with_capacity(names.len());
+    for name in names {
+        if !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    Ok(unique)
+}
+
+/// The variables used in a template that have no value, in the order they are first used. Variables
+/// inside blocks that change the context, like `each`, are not included.
+fn unresolved(reg: &Handlebars, template: &str, vars: &serde_json::Value) -> Vec<String> {
+    let template: Template = match Template::compile(template) {
+        Ok(template) => template,
+        Err(_) => return Vec::new(),
+    };
+    let mut names: Vec<String> = Vec::new();
+    referenced(reg, &template.elements, &mut names, &mut Vec::new());
+
+    let mut unresolved: Vec<String> = Vec::new();
+    for name in names {
+        if lookup(vars, &name).is_none() && !unresolved.contains(&name) {
+            unresolved.push(name);
+        }
+    }
+    unresolved
+}
+
+fn referenced(
+    reg: &Handlebars,
+    elements: &[TemplateElement],
+    names: &mut Vec<String>,
+    partials: &mut Vec<String>,
+) {
+    for element in elements {
+        match element {
+            TemplateElement::Expression(helper) | TemplateElement::HtmlExpression(helper) => {
+                referenced_by_helper(helper, names)
+            }
+            TemplateElement::HelperBlock(helper) => {
+                referenced_by_helper(helper, names);
+                if let Parameter::Name(name) = &helper.name {
+                    if name == "if" || name == "unless" {
+                        let blocks = helper.template.iter().chain(helper.inverse.iter());
+                        blocks.for_each(|block| referenced(reg, &block.elements, names, partials));
+                    }
+                }
+            }
+            TemplateElement::PartialExpression(partial)
+            | TemplateElement::PartialBlock(partial) => {
+                let name: &str = match &partial.name {
+                    Parameter::Name(name) => name,
+                    Parameter::Path(handlebars::Path::Relative((_, raw))) => raw,
+                    _ => continue,
+                };
+                // Each partial is only visited once, which also stops partials including themselves
+                if let Some(template) =
+                    reg.get_template(name).filter(|_| !partials.contains(&name.to_string()))
+                {
+                    partials.push(name.to_string());
+                    referenced(reg, &template.elements, names, partials);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn referenced_by_helper(helper: &HelperTemplate, names: &mut Vec<String>) {
+    let variable: bool = helper.params.is_empty() && helper.hash.is_empty();
+    match &helper.name {
+        Parameter::Name(name) if variable && !is_helper(name) => names.push(name.clone()),
+        name @ Parameter::Path(_) => referenced_by_param(name, names),
+        _ => {}
+    }
+    // The first value given to `default` is expected to be missing at times
+    let skip: usize = match &helper.name {
+        Parameter::Name(name) if name == "default" => 1,
+        _ => 0,
+    };
+    for param in helper.params.iter().skip(skip).chain(helper.hash.values()) {
+        referenced_by_param(param, names);
+    }
+}
+
+fn is_helper(name: &str) -> bool {
+    BUILTIN_HELPERS.contains(&name) || helpers::NAMES.contains(&name)
+}
+
+fn referenced_by_param(param: &Parameter, names: &mut Vec<String>) {
+    match param {
+        Parameter::Path(handlebars::Path::Relative((_, raw)))
+            if !raw.starts_with("this") && !raw.starts_with('.') && !raw.starts_with('@') =>
+        {
+            names.push(raw.clone())
+        }
+        Parameter::Subexpression(subexpression) => {
+            if let TemplateElement::Expression(helper) = subexpression.as_element() {
+                referenced_by_helper(helper, names)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Look up a variable like `captured.token` in the data used when rendering templates
+fn lookup<'a>(vars: &'a serde_json::Value, name: &str) -> Option<&'a serde_json::Value> {
+    let name: &str = name.trim_start_matches('[').trim_end_matches(']');
+    vars.get(name)
+        .or_else(|| name.split(['.', '/']).try_fold(vars, |value, key| value.get(key)))
+}
+
+/// The key of a leaf templated section that `line` starts, which may be quoted, and the rest of the
+/// line after the colon
+fn section_key(line: &str) -> Option<(&'static str, &str)> {
+    LEAF_TEMPLATED_KEYS.iter().find_map(|key| {
+        let rest: &str = ["\"", "'", ""]
+            .iter()
+            .find_map(|quote| line.strip_prefix(quote)?.strip_prefix(key)?.strip_prefix(quote))?;
+        rest.trim_start_matches(' ').strip_prefix(':').map(|rest| (*key, rest))
+    })
+}
+
+/// How many more flow collections, like `{` or `[`, are opened than closed on a line, ignoring
+/// brackets in quoted strings and comments
+fn flow_depth(line: &str) -> isize {
+    let mut depth: isize = 0;
+    let mut quote: Option<char> = None;
+    let mut escaped: bool = false;
+    let mut previous: char = ' ';
+    for c in line.chars() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                previous = c;
+                continue;
+            }
+            (Some(q), c) if c == q && !escaped => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{' | '[') => depth += 1,
+            (None, '}' | ']') => depth -= 1,
+            (None, '#') if previous.is_whitespace() => break,
+            (None, _) => {}
+        }
+        escaped = false;
+        previous = c;
+    }
+    depth
+}
+
+/// A random value which makes placeholders unique
+fn nonce() -> String {
+    let mut bytes = [0u8; 16];
+    openssl::rand::rand_bytes(&mut bytes).expect("Unable to generate random bytes");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn in_section(line: &str, indent: usize) -> bool {
+    let line_indent: usize = indentation(line);
+    line.trim().is_empty()
+        || line_indent > indent
+        || (line_indent == indent && line[indent..].starts_with("- "))
+}
+
+fn starts_block_scalar(line: &str) -> bool {
+    let value: &str = match line.split_once(": ") {
+        Some((_, value)) => value.trim(),
+        None => return false,
+    };
+    let value: &str = value.split(" #").next().unwrap_or_default().trim_end();
+    match value.strip_prefix(['|', '>']) {
+        Some(modifiers) => modifiers.chars().all(|c| c == '-' || c == '+' || c.is_ascii_digit()),
+        None => false,
+    }
+}
+
+/// Build the data used when rendering templates. Keys containing a `.`, such as `captured.token`,
+/// are also made available as nested objects, so they can be referenced as `{{captured.token}}`.
+/// Keys are inserted in sorted order, so that if both `a` and `a.b` are defined, `a` is always the
+/// value of `{{a}}`, and `a.b` can only be used as `{{[a.b]}}`
+fn context(vars: HashMap<String, String>) -> serde_json::Value {
+    let mut root: Map<String, serde_json::Value> = Map::with_capacity(vars.len());
+    let vars: BTreeMap<String, String> = vars.into_iter().collect();
+    for (key, value) in vars {
+        if !insert_nested(&mut root, &key, &value) {
+            log::warn!(
+                "Variable {key} can not be used as a nested value, since a parent is defined"
+            );
+        }
+    }
+    serde_json::Value::Object(root)
+}
+
+/// Insert a value both at its full key and nested under each part of the key, returning false if
+/// it could not be nested since a parent already has a value
+fn insert_nested(map: &mut Map<String, serde_json::Value>, key: &str, value: &str) -> bool {
+    let nested: bool = match key.split_once('.') {
+        Some((head, tail)) => {
+            let entry = map.entry(head).or_insert_with(|| serde_json::Value::Object(Map::new()));
+            match entry {
+                serde_json::Value::Object(inner) => insert_nested(inner, tail, value),
+                _ => false,
+            }
+        }
+        None => true,
+    };
+    map.entry(key).or_insert_with(|| serde_json::Value::String(value.to_string()));
+    nested
 }
 
 fn merge(mut maps: Vec<Property>) -> HashMap<String, String> {
     maps.sort();
 
+    let secrets: HashSet<String> =
+        maps.iter().filter(|p| p.is_secret()).map(|p| p.key().to_string()).collect();
     let vars: HashMap<String, String> = maps
         .into_iter()
         .rev()
         .map(|prop| (prop.key().to_string(), prop.value().to_string()))
         .collect();
 
-    log::debug!("Resolved properties: {:?}", vars);
+    let logged: HashMap<&str, &str> = vars
+        .iter()
+        .map(|(k, v)| (k.as_str(), if secrets.contains(k) { REDACTED } else { v.as_str() }))
+        .collect();
+    log::debug!("Resolved properties: {:?}", logged);
 
     vars
 }
@@ -40,13 +527,13 @@ mod tests {
 
     use crate::prop::{ParsePropertyError, Property, Source};
 
-    use super::merge;
+    use super::{merge, substitution, Options, SubstitutionError};
 
     #[test]
     fn test_merge_properties() -> Result<(), ParsePropertyError> {
         let props: Vec<Property> = vec![
-            Property::new(String::from("key"), String::from("file0"), Source::File(0))?,
-            Property::new(String::from("key"), String::from("file1"), Source::File(1))?,
+            Property::new(String::from("key"), String::from("file0"), Source::File(0, None))?,
+            Property::new(String::from("key"), String::from("file1"), Source::File(1, None))?,
             Property::new(String::from("key"), String::from("env"), Source::EnvVar)?,
             Property::new(String::from("key"), String::from("arg"), Source::Arg)?,
         ];
@@ -56,4 +543,200 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_json_section_is_rendered_per_value() -> Result<(), ParsePropertyError> {
+        let input = r###"
+method: POST
+url: https://{{HOST}}/users
+json:
+  name: "{{NAME}}"
+  tags: [admin, "{{NAME}}"]
+body: |
+  json: this is not a section
+"###;
+        let props: Vec<Property> = vec![
+            Property::new(String::from("HOST"), String::from("example.com"), Source::Arg)?,
+            Property::new(String::from("NAME"), String::from("\"quoted\": yes"), Source::Arg)?,
+        ];
+
+        let output: String = substitution(input.to_string(), props, &Options::default()).unwrap();
+        let doc: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+
+        assert_eq!(doc["url"], "https://example.com/users");
+        assert_eq!(doc["json"]["name"], "\"quoted\": yes");
+        assert_eq!(doc["json"]["tags"][1], "\"quoted\": yes");
+        assert_eq!(doc["body"], "json: this is not a section\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quoted_and_flow_json_sections_are_rendered_per_value() -> Result<(), ParsePropertyError>
+    {
+        let input = r###"
+"url": https://example.com/users
+"json": {
+  "name": "{{NAME}}",
+  "tags": ["a#b", "{{NAME}}"]
+}
+headers:
+  x-section: "{{PATH}}"
+"###;
+        let props: Vec<Property> = vec![
+            Property::new(String::from("NAME"), String::from("\"a\", b: [c"), Source::Arg)?,
+            Property::new(
+                String::from("PATH"),
+                String::from("json: __FIRE_SECTION_0__"),
+                Source::Arg,
+            )?,
+        ];
+
+        let output: String = substitution(input.to_string(), props, &Options::default()).unwrap();
+        let doc: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+
+        assert_eq!(doc["json"]["name"], "\"a\", b: [c");
+        assert_eq!(doc["json"]["tags"][0], "a#b");
+        assert_eq!(doc["json"]["tags"][1], "\"a\", b: [c");
+        assert_eq!(doc["headers"]["x-section"], "json: __FIRE_SECTION_0__");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_request_level_json_is_rendered_per_value() -> Result<(), ParsePropertyError> {
+        let input = r###"
+method: POST
+url: https://example.com/users
+json:
+  id: "{{ID}}"
+capture:
+  token:
+    json: $.token
+expect:
+  json:
+    id: {{ID}}
+"###;
+        let props: Vec<Property> = vec![Property::new(
+            String::from("ID"),
+            String::from("42"),
+            Source::Arg,
+        )?];
+
+        let output: String = substitution(input.to_string(), props, &Options::default()).unwrap();
+        let doc: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+
+        assert_eq!(doc["json"]["id"], "42");
+        assert_eq!(doc["expect"]["json"]["id"], 42);
+        assert_eq!(doc["capture"]["token"]["json"], "$.token");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dotted_keys_are_nested() -> Result<(), ParsePropertyError> {
+        let props: Vec<Property> = vec![
+            Property::new(String::from("captured.token"), String::from("abc"), Source::Captured)?,
+            Property::new(String::from("USER"), String::from("tom"), Source::EnvVar)?,
+        ];
+
+        let output: String =
+            substitution(String::from("{{USER}}: {{captured.token}}"), props, &Options::default())
+                .unwrap();
+        assert_eq!("tom: abc", output);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parent_of_dotted_key_takes_precedence() -> Result<(), ParsePropertyError> {
+        for _ in 0..8 {
+            let props: Vec<Property> = vec![
+                Property::new(String::from("api.url"), String::from("nested"), Source::EnvVar)?,
+                Property::new(String::from("api"), String::from("parent"), Source::EnvVar)?,
+            ];
+
+            let output: String =
+                substitution(String::from("{{api}} {{[api.url]}}"), props, &Options::default())
+                    .unwrap();
+            assert_eq!("parent nested", output);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unresolved_variables_are_listed() -> Result<(), ParsePropertyError> {
+        let input = r###"
+url: https://{{HOST}}/users/{{USER_ID}}
+headers:
+  Authorization: Bearer {{captured.token}}
+{{#if DEBUG}}
+  X-Debug: {{USER_ID}}
+{{/if}}
+{{#each items}}
+  X-Item: {{this}}
+{{/each}}
+"###;
+        let props: Vec<Property> = vec![Property::new(
+            String::from("HOST"),
+            String::from("example.com"),
+            Source::Arg,
+        )?];
+
+        match substitution(input.to_string(), props, &Options::default()) {
+            Err(SubstitutionError::Unresolved(names)) => {
+                assert_eq!(vec!["USER_ID", "captured.token", "DEBUG", "items"], names)
+            }
+            other => panic!("Expected unresolved variables, got {:?}", other),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partials_are_included_with_indentation() -> Result<(), ParsePropertyError> {
+        let mut options = Options::default();
+        options.partials.insert(
+            String::from("auth/headers"),
+            String::from("Authorization: Bearer {{TOKEN}}\nX-Client: fire\n"),
+        );
+        let input = "url: https://example.com\nheaders:\n  {{> auth/headers}}\n";
+
+        match substitution(input.to_string(), Vec::new(), &options) {
+            Err(SubstitutionError::Unresolved(names)) => assert_eq!(vec!["TOKEN"], names),
+            other => panic!("Expected unresolved variables, got {:?}", other),
+        }
+
+        let props = vec![Property::new(
+            String::from("TOKEN"),
+            String::from("abc"),
+            Source::Arg,
+        )?];
+        let output: String = substitution(input.to_string(), props, &options).unwrap();
+        let doc: serde_yaml::Value = serde_yaml::from_str(&output).unwrap();
+        assert_eq!(doc["headers"]["Authorization"], "Bearer abc");
+        assert_eq!(doc["headers"]["X-Client"], "fire");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_external_helpers_require_opt_in() {
+        let render =
+            |input: &str, options: &Options| substitution(input.to_string(), Vec::new(), options);
+        let enabled = Options::default().with_external(true);
+
+        let disabled = render("token: {{exec \"echo abc\"}}", &Options::default());
+        assert!(matches!(disabled, Err(SubstitutionError::External(_))));
+        assert_eq!("token: abc", render("token: {{exec \"echo abc\"}}", &enabled).unwrap());
+        assert!(matches!(
+            render("{{exec \"exit 3\"}}", &enabled),
+            Err(SubstitutionError::External(_))
+        ));
+        assert!(matches!(
+            render("{{file \"does/not/exist\"}}", &enabled),
+            Err(SubstitutionError::External(_))
+        ));
+    }
 }